@@ -0,0 +1,62 @@
+#![feature(test)]
+
+extern crate test;
+
+use std::collections::HashMap;
+
+use syntect::parsing::SyntaxSet;
+use takeoff::{
+  markdown::render_markdown,
+  syntax_highlighting::{highlight_code, SYNTAX_SET},
+  templating::tera_highlight_code,
+};
+use tera::Value;
+use test::Bencher;
+
+/// A Markdown document with a bunch of code blocks to highlight.
+fn markdown_with_code_blocks() -> String {
+  let block =
+    "```rust\nfn main() {\n  println!(\"Hello, Takeoff!\");\n}\n```\n";
+  block.repeat(25)
+}
+
+/// What every code block and filter call used to pay for before the syntaxes
+/// were shared.
+#[bench]
+fn bench_load_syntax_set(bencher: &mut Bencher) {
+  bencher.iter(SyntaxSet::load_defaults_newlines);
+}
+
+/// Highlighting a code block the way it used to be done, loading the
+/// syntaxes for every call. Compare with [`bench_highlight_code_shared`].
+#[bench]
+fn bench_highlight_code_baseline(bencher: &mut Bencher) {
+  bencher.iter(|| {
+    let syntax_set = SyntaxSet::load_defaults_newlines();
+    let syntax = syntax_set.find_syntax_by_token("rust").unwrap();
+    highlight_code("let answer = 42;\n", syntax, &syntax_set)
+  });
+}
+
+/// Highlighting a code block with the shared [`SYNTAX_SET`].
+#[bench]
+fn bench_highlight_code_shared(bencher: &mut Bencher) {
+  bencher.iter(|| {
+    let syntax = SYNTAX_SET.find_syntax_by_token("rust").unwrap();
+    highlight_code("let answer = 42;\n", syntax, &SYNTAX_SET)
+  });
+}
+
+#[bench]
+fn bench_render_markdown(bencher: &mut Bencher) {
+  let markdown = markdown_with_code_blocks();
+  bencher.iter(|| render_markdown(&markdown));
+}
+
+#[bench]
+fn bench_tera_highlight_code(bencher: &mut Bencher) {
+  let code = Value::from("let answer = 42;");
+  let mut args = HashMap::new();
+  args.insert("language".to_string(), Value::from("rust"));
+  bencher.iter(|| tera_highlight_code(&code, &args));
+}
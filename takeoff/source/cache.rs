@@ -0,0 +1,243 @@
+use std::{
+  collections::{hash_map::DefaultHasher, BTreeMap, HashMap, HashSet},
+  fs::{read, read_to_string, write},
+  hash::{Hash, Hasher},
+  path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, instrument, trace};
+
+use crate::{
+  error::{IoResultExt, Result},
+  markdown::{Heading, MarkdownLink},
+};
+
+/// The file name of the [`BuildCache`], stored in
+/// [`Launchpad::output`](crate::Launchpad::output).
+pub const CACHE_FILE_NAME: &str = ".takeoff-cache.json";
+
+/// The [`BuildCache`] keeps track of what went into every output file, so
+/// incremental builds can skip the ones whose inputs haven't changed. See
+/// [`Launchpad::incremental`](crate::Launchpad::incremental) for details.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct BuildCache {
+  /// The rendered Markdown of every
+  /// [`Document`](crate::document::Document), keyed by the path of its source
+  /// file.
+  pub documents: HashMap<PathBuf, CachedDocument>,
+  /// The fingerprint of every output file, keyed by its path relative to
+  /// [`Launchpad::output`](crate::Launchpad::output).
+  pub outputs: HashMap<PathBuf, u64>,
+  /// The content hash and dependencies of every template, keyed by its name.
+  pub templates: HashMap<String, CachedTemplate>,
+}
+
+/// The cached rendering of a [`Document`](crate::document::Document).
+#[derive(Debug, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct CachedDocument {
+  /// The HTML that was rendered from the Markdown.
+  pub content: String,
+  /// The [`hash`] of the Markdown and the settings that affect how it's
+  /// rendered, like [`Launchpad::base_url`](crate::Launchpad::base_url).
+  pub hash: u64,
+  /// The links and images collected from the Markdown.
+  pub links: Vec<MarkdownLink>,
+  /// The plain text collected from the Markdown.
+  pub text: String,
+  /// The table of contents collected from the Markdown.
+  pub toc: Vec<Heading>,
+}
+
+/// A template in the dependency graph of the [`BuildCache`].
+#[derive(Debug, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct CachedTemplate {
+  /// The templates this one `extends`, `include`s or `import`s.
+  pub dependencies: Vec<String>,
+  /// The [`hash`] of the template file.
+  pub hash: u64,
+  /// Whether this template uses the `content` or `toc` of a
+  /// [`Document`](crate::document::Document) other than `document`, see
+  /// [`BuildCache::uses_other_content`].
+  pub uses_other_content: bool,
+}
+
+impl BuildCache {
+  /// Loads the [`BuildCache`] from a file. If the file doesn't exist or can't
+  /// be parsed, an empty cache is returned so everything gets rebuilt.
+  #[instrument]
+  pub fn load(path: &Path) -> Self {
+    read_to_string(path)
+      .ok()
+      .and_then(|source| serde_json::from_str(&source).ok())
+      .unwrap_or_else(|| {
+        debug!("No usable build cache found, starting from scratch");
+        Self::default()
+      })
+  }
+
+  /// Writes the [`BuildCache`] to a file.
+  pub fn save(&self, path: &Path) -> Result<()> {
+    write(path, serde_json::to_string(self)?).at(path)
+  }
+
+  /// Checks whether the output file at `key` was generated from inputs with
+  /// the same fingerprint and still exists at `destination`.
+  pub fn is_fresh(
+    &self,
+    key: &Path,
+    fingerprint: u64,
+    destination: &Path,
+  ) -> bool {
+    self.outputs.get(key) == Some(&fingerprint) && destination.exists()
+  }
+
+  /// Returns a fingerprint of the template `name` and every template it
+  /// depends on, looking for their files in `directory`.
+  ///
+  /// Templates that don't exist as a file in `directory` are fingerprinted as
+  /// empty, any changes to them will have to trigger a full rebuild.
+  pub fn template_fingerprint(&mut self, directory: &Path, name: &str) -> u64 {
+    let hashes = self
+      .template_closure(directory, name)
+      .into_iter()
+      .map(|name| {
+        let hash = self.templates[&name].hash;
+        (name, hash)
+      })
+      .collect::<BTreeMap<_, _>>();
+
+    hash(&hashes)
+  }
+
+  /// Checks whether the template `name` or any template it depends on uses the
+  /// `content` or `toc` of documents other than `document`, like through
+  /// `documents`, `paginator.pages` or `previous`. Pages rendered with such a
+  /// template have to be rendered again when any document's content changes.
+  ///
+  /// This looks for the names inside Tera tags, so templates that pass whole
+  /// documents to a filter like `json_encode` aren't noticed.
+  pub fn uses_other_content(&mut self, directory: &Path, name: &str) -> bool {
+    self
+      .template_closure(directory, name)
+      .iter()
+      .any(|name| self.templates[name].uses_other_content)
+  }
+
+  /// Returns the names of the template `name` and every template it depends
+  /// on, making sure all of them are in [`BuildCache::templates`].
+  fn template_closure(&mut self, directory: &Path, name: &str) -> Vec<String> {
+    let mut queue = vec![name.to_string()];
+    let mut visited = HashSet::new();
+
+    while let Some(name) = queue.pop() {
+      if visited.contains(&name) {
+        continue;
+      }
+
+      let template = self.template(directory, &name);
+      queue.extend(template.dependencies.iter().cloned());
+      visited.insert(name);
+    }
+
+    visited.into_iter().collect()
+  }
+
+  /// Returns the [`CachedTemplate`] for `name`, updating its dependencies when
+  /// the template file has changed.
+  fn template(&mut self, directory: &Path, name: &str) -> &CachedTemplate {
+    let source = read_to_string(directory.join(name)).unwrap_or_default();
+    let source_hash = hash(&source);
+
+    let cached = self.templates.get(name);
+    if !matches!(cached, Some(template) if template.hash == source_hash) {
+      trace!("Scanning dependencies of template {}", name);
+      let template = CachedTemplate {
+        dependencies: template_references(&source),
+        hash: source_hash,
+        uses_other_content: uses_other_content(&source),
+      };
+      self.templates.insert(name.to_string(), template);
+    }
+
+    &self.templates[name]
+  }
+}
+
+/// Hashes anything that implements [`Hash`]. The result is only meant to be
+/// compared against other hashes made by the same Takeoff binary.
+pub fn hash<T: Hash + ?Sized>(value: &T) -> u64 {
+  let mut hasher = DefaultHasher::new();
+  value.hash(&mut hasher);
+  hasher.finish()
+}
+
+/// Hashes the contents of a file, returning `0` if it can't be read.
+pub fn hash_file(path: &Path) -> u64 {
+  read(path).map(|bytes| hash(&bytes)).unwrap_or_default()
+}
+
+/// Checks whether the Tera tags in the source of a template use `content` or
+/// `toc` on anything but `document`.
+fn uses_other_content(source: &str) -> bool {
+  let is_identifier = |c: char| c.is_ascii_alphanumeric() || c == '_';
+  let tags = source.split("{{").skip(1).chain(source.split("{%").skip(1));
+
+  tags
+    .filter_map(|tag| tag.split(['}', '%']).next())
+    .any(|tag| {
+      ["content", "toc"].iter().any(|name| {
+        tag.match_indices(name).any(|(start, _)| {
+          let before = tag[..start].trim_end();
+          let after = &tag[start + name.len()..];
+          let on_document = before
+            .strip_suffix("document.")
+            .is_some_and(|rest| !rest.ends_with(is_identifier));
+          !tag[..start].ends_with(is_identifier)
+            && !after.starts_with(is_identifier)
+            && !on_document
+        })
+      })
+    })
+}
+
+/// Finds the names of the templates referenced by `extends`, `include` and
+/// `import` tags in the source of a template.
+fn template_references(source: &str) -> Vec<String> {
+  let mut references = vec![];
+
+  for tag in source.split("{%").skip(1) {
+    let tag = tag.split("%}").next().unwrap_or_default();
+    let tag = tag.trim_start_matches('-').trim_start();
+
+    let is_reference = ["extends", "include", "import"]
+      .iter()
+      .any(|keyword| tag.starts_with(keyword));
+    if !is_reference {
+      continue;
+    }
+
+    // Collect every quoted string, `include` can take an array of templates.
+    let mut quote = None;
+    let mut name = String::new();
+    for character in tag.chars() {
+      match quote {
+        Some(open) if character == open => {
+          references.push(name.clone());
+          name.clear();
+          quote = None;
+        }
+        Some(_) => name.push(character),
+        None if matches!(character, '"' | '\'' | '`') => {
+          quote = Some(character)
+        }
+        None => (),
+      }
+    }
+  }
+
+  references
+}
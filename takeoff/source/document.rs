@@ -1,99 +1,269 @@
 use std::{
+  collections::BTreeMap,
+  ffi::OsStr,
+  fmt,
   fs::{create_dir_all, read_to_string},
-  path::PathBuf,
+  path::{Path, PathBuf},
 };
 
-use anyhow::Result;
-use serde::{Deserialize, Serialize};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use toml::value::Table;
 use tracing::{debug, instrument};
 
-use crate::Launchpad;
+use crate::{
+  error::{Error, IoResultExt, Result},
+  markdown::{Heading, MarkdownLink},
+  section::SortBy,
+  Launchpad,
+};
 
 /// A [`Document`] describes a Markdown file found in a [`Launchpad`]'s
 /// [`source`](Launchpad::source) directory.
 #[derive(Debug, Deserialize, Serialize)]
 #[non_exhaustive]
 pub struct Document {
-  /// The content of the Markdown file without the `<!-- Metadata -->` block if
-  /// it was present.
+  /// The content of the Markdown file without the front matter if it was
+  /// present.
   pub content: String,
+  /// The kind of front matter the [`Metadata`] was parsed from.
+  pub front_matter: FrontMatter,
+  /// The number of lines before [`Document::content`] in the Markdown file,
+  /// to turn lines in the content into lines in the file.
+  #[serde(skip)]
+  pub front_matter_lines: usize,
+  /// The links and images in the Markdown, with their lines in
+  /// [`Document::content`].
+  ///
+  /// This isn't passed to templates, it's used by
+  /// [`Launchpad::check_links`] to report broken links with their line.
+  #[serde(skip)]
+  pub links: Vec<MarkdownLink>,
   /// The parsed metadata.
   pub metadata: Metadata,
+  /// The path of the HTML file this [`Document`] is written to, relative to
+  /// [`Launchpad::output`]. See [`Document::destination`] for details.
+  pub output_path: PathBuf,
+  /// The URL of this [`Document`] relative to the root of the site, like
+  /// `/blog/first-post.html` or `/blog/first-post/` with pretty URLs. See
+  /// [`Launchpad::pretty_urls`] for details.
+  pub permalink: String,
+  /// The path of the [`Section`](crate::section::Section) this [`Document`]
+  /// is in, relative to [`Launchpad::source`].
+  pub section: PathBuf,
   /// The absolute path of the source Markdown file.
+  ///
+  /// This isn't passed to templates, since it would leak paths from the
+  /// machine the site was built on.
+  #[serde(skip)]
   pub source_path: PathBuf,
+  /// The plain text of the Markdown, see
+  /// [`RenderedMarkdown::text`](crate::markdown::RenderedMarkdown).
+  ///
+  /// This isn't passed to templates, it's used for the
+  /// [`SearchIndex`](crate::search::SearchIndex).
+  #[serde(skip)]
+  pub text: String,
+  /// The table of contents of the Markdown, made from its headings. Templates
+  /// can link to every [`Heading`] with `#` and its `id`.
+  pub toc: Vec<Heading>,
+  /// The root-relative URL to link to this [`Document`] with, which is the
+  /// [`Document::permalink`] with the path of [`Launchpad::base_url`] in front
+  /// of it. See [`Launchpad::url`] for details.
+  pub url: String,
 }
 
 impl Document {
   /// Creates a new [`Document`] from a file. If parsing [`Metadata`] fails for
-  /// this document, then an error is printed and [`Metadata::default()`] will
-  /// be used instead.
+  /// this document, then an [`Error::Metadata`] is returned that includes the
+  /// path and line where it went wrong.
+  ///
+  /// The [`Document::output_path`], [`Document::permalink`],
+  /// [`Document::section`] and [`Document::url`] are left empty, they're
+  /// filled in when the document is collected by
+  /// [`Launchpad::parse_documents`].
   #[instrument]
   pub fn new(path: PathBuf) -> Result<Self> {
     debug!("Parsing");
-    let (metadata, source) = Metadata::parse(&read_to_string(&path)?);
-    let metadata = metadata.unwrap_or_else(|err| {
-      eprintln!(
-        "Error parsing metadata for {:?} (using default): {}",
-        path, err
-      );
-      Metadata::default()
-    });
+    let file = read_to_string(&path).at(&path)?;
+    let (metadata, source, front_matter) = Metadata::parse(&file);
+    let metadata = metadata.map_err(|err| err.with_path(path.clone()))?;
+    let front_matter_lines =
+      file[..file.len() - source.len()].matches('\n').count();
 
     let document = Self {
       content: source,
+      front_matter,
+      front_matter_lines,
+      links: vec![],
       metadata,
+      output_path: PathBuf::new(),
+      permalink: String::new(),
+      section: PathBuf::new(),
       source_path: path,
+      text: String::new(),
+      toc: vec![],
+      url: String::new(),
     };
     Ok(document)
   }
 
-  /// Creates all the directories required and returns the HTML path where
-  /// this [`Document`] should be written to.
-  pub fn create_destination(&self, launchpad: &Launchpad) -> Result<PathBuf> {
-    let parent_dirs = self
-      .source_path
-      .strip_prefix(&launchpad.source)?
-      .parent()
-      .unwrap();
+  /// Returns the HTML path where this [`Document`] should be written to. The
+  /// file name is the [`Metadata::slug`] if there is one, otherwise the
+  /// source file's name.
+  ///
+  /// With [`Launchpad::pretty_urls`] (or [`Metadata::pretty_url`]) enabled the
+  /// file is written as `index.html` inside a directory with that name
+  /// instead, except for `index` itself, which stays the directory's index.
+  pub fn destination(&self, launchpad: &Launchpad) -> Result<PathBuf> {
+    let output = launchpad.output.join(self.source_directory(launchpad)?);
+    let file_stem = match &self.metadata.slug {
+      Some(slug) => slug.clone(),
+      None => self
+        .source_path
+        .file_stem()
+        .unwrap()
+        .to_string_lossy()
+        .into(),
+    };
+
+    let pretty_url = self.metadata.pretty_url.unwrap_or(launchpad.pretty_urls);
+    if pretty_url && file_stem != "index" {
+      Ok(output.join(file_stem).join("index.html"))
+    } else {
+      Ok(output.join(file_stem + ".html"))
+    }
+  }
 
-    let output = launchpad.output.join(parent_dirs);
+  /// Returns the directory of the source file relative to
+  /// [`Launchpad::source`], which is also the path of the
+  /// [`Section`](crate::section::Section) this [`Document`] is in.
+  pub fn source_directory(&self, launchpad: &Launchpad) -> Result<&Path> {
+    let relative =
+      self
+        .source_path
+        .strip_prefix(&launchpad.source)
+        .map_err(|_| Error::OutsideDirectory {
+          directory: launchpad.source.clone(),
+          path: self.source_path.clone(),
+        })?;
+    Ok(relative.parent().unwrap())
+  }
 
-    create_dir_all(&output)?;
-    let file_stem = self.source_path.file_stem().unwrap();
-    Ok(output.join(file_stem).with_extension("html"))
+  /// Checks whether this [`Document`] is an `index.md` file, which makes it
+  /// the page of its [`Section`](crate::section::Section).
+  pub fn is_section_index(&self) -> bool {
+    self.source_path.file_stem() == Some(OsStr::new("index"))
   }
+
+  /// Creates all the directories required and returns the
+  /// [`Document::destination`].
+  pub fn create_destination(&self, launchpad: &Launchpad) -> Result<PathBuf> {
+    let destination = self.destination(launchpad)?;
+    let parent = destination.parent().unwrap();
+    create_dir_all(parent).at(parent)?;
+    Ok(destination)
+  }
+}
+
+/// The kinds of front matter [`Metadata`] can be parsed from.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[non_exhaustive]
+pub enum FrontMatter {
+  /// No front matter was present.
+  None,
+  /// TOML inside a `<!-- Metadata -->` HTML comment block.
+  Comment,
+  /// TOML between two `+++` lines.
+  Toml,
+  /// YAML between two `---` lines.
+  Yaml,
 }
 
-/// [`Metadata`] contains all the data found in a [`Document`]'s Markdown
-/// `<!-- Metadata -->` block.
+/// [`Metadata`] contains all the data found in a [`Document`]'s front matter.
+/// See [`Metadata::parse`] for the supported kinds.
 #[derive(Debug, Deserialize, Serialize)]
 #[non_exhaustive]
 pub struct Metadata {
+  /// The date this [`Document`] was published.
+  #[serde(default)]
+  pub date: Option<Date>,
+  /// A short description of this [`Document`].
+  #[serde(default)]
+  pub description: Option<String>,
   /// Extra custom metadata to include when rendering this [`Document`].
   #[serde(default)]
   pub extra: Table,
   /// A boolean indicating whether this [`Document`] should be ignored entirely.
+  /// Ignored documents are never rendered and aren't included in
+  /// [`Launchpad::parse_documents`], regardless of [`Launchpad::mode`].
   #[serde(default)]
   pub ignore: bool,
   /// A boolean indicating whether this [`Document`] is a draft. See
   /// [`Launchpad::mode`] for details.
   #[serde(default = "Metadata::is_draft_default")]
   pub is_draft: bool,
+  /// Splits the pages of the [`Section`](crate::section::Section) this
+  /// [`Document`] is the `index.md` of into pages of this many, see
+  /// [`Paginator`](crate::pagination::Paginator) for details. It has no
+  /// effect on other documents.
+  #[serde(default)]
+  pub paginate_by: Option<usize>,
+  /// Overrides [`Launchpad::pretty_urls`] for this [`Document`].
+  #[serde(default)]
+  pub pretty_url: Option<bool>,
+  /// The file name to use for this [`Document`]'s output, without the
+  /// extension. See [`Document::create_destination`] for details.
+  #[serde(default)]
+  pub slug: Option<String>,
+  /// How to sort the pages of the [`Section`](crate::section::Section) this
+  /// [`Document`] is the `index.md` of. It has no effect on other documents.
+  ///
+  /// Defaults to [`SortBy::Path`].
+  #[serde(default)]
+  pub sort_by: SortBy,
+  /// The terms of every [`Taxonomy`](crate::taxonomy::Taxonomy) this
+  /// [`Document`] belongs to, keyed by the taxonomy's name.
+  ///
+  /// ```toml
+  /// [taxonomies]
+  /// tags = ["rust", "static sites"]
+  /// ```
+  #[serde(default)]
+  pub taxonomies: BTreeMap<String, Vec<String>>,
   /// The template to use for this [`Document`].
   ///
   /// Relative to [`Templates::directory`](crate::launchpad::Templates).
   #[serde(default)]
   pub template: Option<String>,
+  /// The title of this [`Document`].
+  #[serde(default)]
+  pub title: Option<String>,
+  /// The date this [`Document`] was last updated.
+  #[serde(default)]
+  pub updated: Option<Date>,
+  /// The weight of this [`Document`], used with [`SortBy::Weight`].
+  #[serde(default)]
+  pub weight: Option<i64>,
 }
 
 impl Default for Metadata {
   fn default() -> Self {
     Self {
+      date: None,
+      description: None,
       extra: Table::default(),
       ignore: false,
       is_draft: Self::is_draft_default(),
+      paginate_by: None,
+      pretty_url: None,
+      slug: None,
+      sort_by: SortBy::default(),
+      taxonomies: BTreeMap::new(),
       template: None,
+      title: None,
+      updated: None,
+      weight: None,
     }
   }
 }
@@ -101,45 +271,184 @@ impl Default for Metadata {
 impl Metadata {
   /// Tries to parse [`Metadata`] from a TOML string with [`serde`] and [`toml`].
   pub fn from_toml(source: &str) -> Result<Self> {
-    toml::from_str(source).map_err(Into::into)
+    Self::parse_toml(source, 1)
+  }
+
+  /// Tries to parse [`Metadata`] from a YAML string with [`serde`] and
+  /// [`serde_yaml`].
+  pub fn from_yaml(source: &str) -> Result<Self> {
+    Self::parse_yaml(source, 1)
+  }
+
+  /// [`Metadata::from_toml`] for TOML that starts on line `first_line`.
+  fn parse_toml(source: &str, first_line: usize) -> Result<Self> {
+    toml::from_str::<Self>(source)
+      .map_err(|err| Error::toml_metadata(err, first_line))?
+      .validate()
+  }
+
+  /// [`Metadata::from_yaml`] for YAML that starts on line `first_line`.
+  fn parse_yaml(source: &str, first_line: usize) -> Result<Self> {
+    serde_yaml::from_str::<Self>(source)
+      .map_err(|err| Error::yaml_metadata(err, first_line))?
+      .validate()
   }
 
-  /// Tries to parse [`Metadata`] from a Markdown string's
-  /// `<!-- Metadata -->` block.
+  /// Checks the values that can't be checked while deserializing.
+  fn validate(self) -> Result<Self> {
+    if let Some(slug) = &self.slug {
+      if slug.is_empty() || slug.contains(['/', '\\']) || slug == ".." {
+        return Err(Error::Metadata {
+          line: None,
+          message: format!("Invalid slug {:?}", slug),
+          path: None,
+        });
+      }
+    }
+
+    Ok(self)
+  }
+
+  /// Tries to parse [`Metadata`] from a Markdown string's front matter, which
+  /// can be any of the following:
+  ///
+  /// * A `<!-- Metadata -->` block containing TOML.
+  /// * TOML between two `+++` lines, like in Hugo and Zola.
+  /// * YAML between two `---` lines, like in Hugo and Jekyll.
   ///
-  /// If the Markdown string does not start with a `<!-- Metadata -->` block
-  /// then `Metadata::default()` is returned in the [`Result`] and the
-  /// Markdown as the String.
+  /// If the Markdown string does not start with front matter then
+  /// `Metadata::default()` is returned in the [`Result`], the Markdown as the
+  /// String and [`FrontMatter::None`].
   ///
-  /// If the Markdown does start with a `<!-- Metadata -->` block then the
-  /// contents inside it will try to parse a [`Metadata`] with
-  /// [`Metadata::from_toml()`]. The returned Markdown string will then also
-  /// have the Metadata block removed.
+  /// If the Markdown does start with front matter then the contents inside it
+  /// will try to parse a [`Metadata`] with [`Metadata::from_toml()`] or
+  /// [`Metadata::from_yaml()`]. The returned Markdown string will then also
+  /// have the front matter removed, and the [`FrontMatter`] says which kind
+  /// was found.
   #[instrument(skip(source))]
-  pub fn parse(source: &str) -> (Result<Self>, String) {
-    const METADATA_START: &str = "<!-- Metadata\n";
-    const METADATA_END: &str = "\n-->";
+  pub fn parse(source: &str) -> (Result<Self>, String, FrontMatter) {
+    const DELIMITERS: &[(FrontMatter, &str, &str)] = &[
+      (FrontMatter::Comment, "<!-- Metadata", "-->"),
+      (FrontMatter::Toml, "+++", "+++"),
+      (FrontMatter::Yaml, "---", "---"),
+    ];
 
-    // If no metadata is included, return default metadata.
-    if !source.starts_with(METADATA_START) && !source.contains(METADATA_END) {
-      debug!("No Metadata block found, returning early");
-      return (Ok(Self::default()), source.to_string());
-    }
+    for (front_matter, start, end) in DELIMITERS {
+      // Both delimiters have to be on a line of their own.
+      let metadata_start = match source
+        .strip_prefix(start)
+        .and_then(|rest| rest.strip_prefix('\n').or(rest.strip_prefix("\r\n")))
+      {
+        Some(rest) => source.len() - rest.len(),
+        None => continue,
+      };
+
+      // Grab the start index of the closing delimiter's line.
+      let mut end_index = None;
+      let mut line_start = metadata_start;
+      for line in source[metadata_start..].split_inclusive('\n') {
+        if line.trim_end_matches('\n').trim_end_matches('\r') == *end {
+          end_index = Some(line_start);
+          break;
+        }
+
+        line_start += line.len();
+      }
 
-    // Grab the start and end indexes of the metadata TOML.
-    let start = source.find(METADATA_START).unwrap() + METADATA_START.len();
-    let end = source.find(METADATA_END).unwrap();
+      let end_index = match end_index {
+        Some(index) => index,
+        None => continue,
+      };
 
-    // Get the metadata TOML itself.
-    let metadata_toml = &source[start..end];
+      // Get the front matter itself.
+      let metadata_source = &source[metadata_start..end_index];
 
-    // Remove the metadata from the Markdown.
-    let new_input = source[end + METADATA_END.len()..].to_string();
+      // Remove the front matter from the Markdown, keeping the line ending of
+      // the closing delimiter.
+      let new_input = source[end_index + end.len()..].to_string();
 
-    (Self::from_toml(metadata_toml), new_input)
+      debug!("Found {:?} front matter", front_matter);
+      // The front matter starts on the line after the opening delimiter.
+      let metadata = match front_matter {
+        FrontMatter::Yaml => Self::parse_yaml(metadata_source, 2),
+        _ => Self::parse_toml(metadata_source, 2),
+      };
+
+      return (metadata, new_input, *front_matter);
+    }
+
+    // If no metadata is included, return default metadata.
+    debug!("No front matter found, returning early");
+    (Ok(Self::default()), source.to_string(), FrontMatter::None)
   }
 
   pub(crate) fn is_draft_default() -> bool {
     true
   }
 }
+
+/// A date in [`Metadata`], either a full RFC 3339 date and time like
+/// `2021-01-31T12:00:00+01:00` or only a date like `2021-01-31`, which will be
+/// at midnight UTC. Dates and times without an offset are also assumed to be in
+/// UTC.
+///
+/// Dates are serialized as RFC 3339 strings converted to UTC, so templates can
+/// sort [`Document`]s by them and use them with Tera's `date` filter.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Date(pub DateTime<FixedOffset>);
+
+impl Date {
+  /// Tries to parse a [`Date`] from a string.
+  pub fn parse(source: &str) -> Result<Self> {
+    let utc = FixedOffset::east_opt(0).unwrap();
+
+    if let Ok(date) = DateTime::parse_from_rfc3339(source) {
+      return Ok(Self(date));
+    }
+
+    for format in &["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"] {
+      if let Ok(date) = NaiveDateTime::parse_from_str(source, format) {
+        return Ok(Self(utc.from_utc_datetime(&date)));
+      }
+    }
+
+    let date = NaiveDate::parse_from_str(source, "%Y-%m-%d")
+      .map_err(|_| Error::Date(source.to_string()))?;
+    Ok(Self(
+      utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap()),
+    ))
+  }
+}
+
+impl fmt::Display for Date {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.0.with_timezone(&Utc).to_rfc3339())
+  }
+}
+
+impl<'de> Deserialize<'de> for Date {
+  fn deserialize<D: Deserializer<'de>>(
+    deserializer: D,
+  ) -> Result<Self, D::Error> {
+    /// TOML has its own date type, everything else uses strings.
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawDate {
+      String(String),
+      Toml(toml::value::Datetime),
+    }
+
+    let source = match RawDate::deserialize(deserializer)? {
+      RawDate::String(source) => source,
+      RawDate::Toml(datetime) => datetime.to_string(),
+    };
+
+    Self::parse(&source).map_err(serde::de::Error::custom)
+  }
+}
+
+impl Serialize for Date {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(self)
+  }
+}
@@ -0,0 +1,367 @@
+use std::{fmt, io, path::PathBuf};
+
+use crate::links::BrokenLink;
+
+/// A [`Result`](std::result::Result) with Takeoff's [`Error`] as the default
+/// error type.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// All the errors that can happen while using Takeoff.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+  /// [`Launchpad::deny_broken_links`](crate::Launchpad::deny_broken_links)
+  /// is enabled and the generated site has broken links.
+  BrokenLinks(Vec<BrokenLink>),
+  /// Parsing a configuration file or string failed.
+  Config {
+    /// The configuration file, if the configuration came from one.
+    path: Option<PathBuf>,
+    /// The error from [`toml`].
+    source: toml::de::Error,
+  },
+  /// A date in [`Metadata`](crate::document::Metadata) couldn't be parsed.
+  Date(String),
+  /// Two [`Document`](crate::document::Document)s would be written to the
+  /// same output file, for example `foo.md` and `foo/index.md` with
+  /// [`Launchpad::pretty_urls`](crate::Launchpad::pretty_urls) enabled.
+  DuplicateOutput {
+    /// The Markdown file of the first document.
+    first: PathBuf,
+    /// The output file both documents would be written to.
+    output_path: PathBuf,
+    /// The Markdown file of the second document.
+    second: PathBuf,
+  },
+  /// A page generated by Takeoff, like a [`Taxonomy`](crate::taxonomy::Taxonomy)
+  /// page or a feed, would be written to the same output file as a
+  /// [`Document`](crate::document::Document) or another generated page.
+  GeneratedOutput {
+    /// The Markdown file of the document that would be overwritten, or `None`
+    /// when it's another generated page.
+    document: Option<PathBuf>,
+    /// The output file both pages would be written to.
+    output_path: PathBuf,
+    /// The template of the generated page.
+    template: String,
+  },
+  /// Processing a static file's glob pattern failed.
+  Glob {
+    /// The glob pattern.
+    pattern: String,
+    /// The error from [`glob`].
+    source: glob::PatternError,
+  },
+  /// Reading or writing a file or directory failed.
+  Io {
+    /// The file or directory.
+    path: PathBuf,
+    /// The underlying error.
+    source: io::Error,
+  },
+  /// (De)serializing JSON failed, for example for the
+  /// [`BuildCache`](crate::cache::BuildCache).
+  Json(serde_json::Error),
+  /// Parsing a [`Document`](crate::document::Document)'s metadata failed.
+  Metadata {
+    /// The line in the file where the error happened, if it's known.
+    line: Option<usize>,
+    /// What went wrong.
+    message: String,
+    /// The Markdown file, if the metadata came from one.
+    path: Option<PathBuf>,
+  },
+  /// A link in the Markdown of a [`Document`](crate::document::Document)
+  /// points to a `.md` file that isn't a document, see
+  /// [`MarkdownOptions::permalinks`](crate::markdown::MarkdownOptions).
+  MissingDocument {
+    /// The line in the Markdown file the link is on.
+    line: usize,
+    /// The Markdown file with the link.
+    path: PathBuf,
+    /// The URL of the link.
+    url: String,
+  },
+  /// Multiple errors happened, for example while rendering
+  /// [`Document`](crate::document::Document)s in parallel.
+  Multiple(Vec<Error>),
+  /// A path that should've been inside a directory wasn't.
+  OutsideDirectory {
+    /// The directory.
+    directory: PathBuf,
+    /// The path.
+    path: PathBuf,
+  },
+  /// Compiling a Sass file failed.
+  Sass {
+    /// What went wrong.
+    message: String,
+    /// The Sass file.
+    path: PathBuf,
+  },
+  /// A shortcode in the Markdown of a
+  /// [`Document`](crate::document::Document) is invalid, see
+  /// [`render_shortcodes`](crate::shortcode::render_shortcodes).
+  Shortcode {
+    /// The line in the file where the shortcode starts.
+    line: usize,
+    /// What went wrong.
+    message: String,
+    /// The Markdown file, if the shortcode came from one.
+    path: Option<PathBuf>,
+  },
+  /// Starting the development server failed.
+  Server {
+    /// The address the server tried to listen on.
+    address: String,
+    /// The underlying error.
+    source: io::Error,
+  },
+  /// The name of a [`Taxonomy`](crate::taxonomy::Taxonomy) isn't a slug.
+  Taxonomy(String),
+  /// Loading or rendering templates failed.
+  Template {
+    /// The Markdown file that was being rendered, if any.
+    document: Option<PathBuf>,
+    /// The template that was being rendered, if any.
+    name: Option<String>,
+    /// The error from [`tera`].
+    source: tera::Error,
+  },
+  /// Creating the thread pool failed.
+  ThreadPool(rayon::ThreadPoolBuildError),
+  /// Watching for changes failed.
+  Watch(notify::Error),
+}
+
+impl Error {
+  /// Creates an [`Error::Metadata`] from a [`toml`] error, with the line
+  /// number (if any) offset by `first_line`.
+  pub(crate) fn toml_metadata(err: toml::de::Error, first_line: usize) -> Self {
+    Self::Metadata {
+      line: err.line_col().map(|(line, _)| line + first_line),
+      message: strip_location(err.to_string()),
+      path: None,
+    }
+  }
+
+  /// Creates an [`Error::Metadata`] from a [`serde_yaml`] error, with the line
+  /// number (if any) offset by `first_line`.
+  pub(crate) fn yaml_metadata(
+    err: serde_yaml::Error,
+    first_line: usize,
+  ) -> Self {
+    Self::Metadata {
+      line: err
+        .location()
+        .map(|location| location.line() - 1 + first_line),
+      message: strip_location(err.to_string()),
+      path: None,
+    }
+  }
+
+  /// Sets the path of an [`Error::Metadata`], other errors are returned as-is.
+  pub(crate) fn with_path(self, path: PathBuf) -> Self {
+    match self {
+      Self::Metadata { line, message, .. } => Self::Metadata {
+        line,
+        message,
+        path: Some(path),
+      },
+      err => err,
+    }
+  }
+}
+
+/// Removes the location that [`toml`] and [`serde_yaml`] add to their error
+/// messages, since it's relative to the start of the metadata and not the
+/// file, [`Error::Metadata`] keeps track of the line itself.
+fn strip_location(message: String) -> String {
+  match message.rfind(" at line ") {
+    Some(index) => message[..index].to_string(),
+    None => message,
+  }
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Self::BrokenLinks(links) => {
+        write!(f, "{} broken links found:", links.len())?;
+        for link in links {
+          write!(f, "\n{}", link)?;
+        }
+
+        Ok(())
+      }
+      Self::Config { path, source } => match path {
+        Some(path) => write!(f, "Error parsing config {:?}: {}", path, source),
+        None => write!(f, "Error parsing config: {}", source),
+      },
+      Self::Date(date) => write!(f, "Invalid date {:?}", date),
+      Self::DuplicateOutput {
+        first,
+        output_path,
+        second,
+      } => write!(
+        f,
+        "{:?} and {:?} would both be written to {:?}",
+        first, second, output_path
+      ),
+      Self::GeneratedOutput {
+        document,
+        output_path,
+        template,
+      } => match document {
+        Some(document) => write!(
+          f,
+          "The page generated with template {:?} would overwrite {:?} at {:?}",
+          template, document, output_path
+        ),
+        None => write!(
+          f,
+          "The page generated with template {:?} would overwrite another \
+           generated page at {:?}",
+          template, output_path
+        ),
+      },
+      Self::Glob { pattern, source } => {
+        write!(f, "Invalid glob {:?}: {}", pattern, source)
+      }
+      Self::Io { path, source } => write!(f, "{:?}: {}", path, source),
+      Self::Json(source) => write!(f, "Error (de)serializing JSON: {}", source),
+      Self::Metadata {
+        line,
+        message,
+        path,
+      } => {
+        write!(f, "Error parsing metadata")?;
+        if let Some(path) = path {
+          write!(f, " for {:?}", path)?;
+        }
+
+        if let Some(line) = line {
+          write!(f, " on line {}", line)?;
+        }
+
+        write!(f, ": {}", message)
+      }
+      Self::MissingDocument { line, path, url } => write!(
+        f,
+        "{}:{}: Link to missing document {:?}",
+        path.display(),
+        line,
+        url
+      ),
+      Self::Multiple(errors) => {
+        write!(f, "{} errors occurred:", errors.len())?;
+        for err in errors {
+          write!(f, "\n{}", err)?;
+        }
+
+        Ok(())
+      }
+      Self::OutsideDirectory { directory, path } => {
+        write!(f, "{:?} is not inside {:?}", path, directory)
+      }
+      Self::Sass { message, path } => {
+        write!(f, "Error compiling Sass {:?}: {}", path, message)
+      }
+      Self::Shortcode {
+        line,
+        message,
+        path,
+      } => {
+        write!(f, "Error parsing shortcode")?;
+        if let Some(path) = path {
+          write!(f, " for {:?}", path)?;
+        }
+
+        write!(f, " on line {}: {}", line, message)
+      }
+      Self::Server { address, source } => {
+        write!(f, "Error serving on {}: {}", address, source)
+      }
+      Self::Taxonomy(message) => write!(f, "{}", message),
+      Self::Template {
+        document,
+        name,
+        source,
+      } => {
+        match (name, document) {
+          (Some(name), Some(document)) => {
+            write!(f, "Error rendering template {:?} for {:?}", name, document)?
+          }
+          (Some(name), None) => {
+            write!(f, "Error rendering template {:?}", name)?
+          }
+          _ => write!(f, "Error loading templates")?,
+        }
+
+        // Tera puts the actual cause of an error in its source, so include
+        // the whole chain to make the message useful.
+        let mut source: Option<&dyn std::error::Error> = Some(source);
+        while let Some(err) = source {
+          write!(f, ": {}", err)?;
+          source = err.source();
+        }
+
+        Ok(())
+      }
+      Self::ThreadPool(source) => {
+        write!(f, "Error creating thread pool: {}", source)
+      }
+      Self::Watch(source) => {
+        write!(f, "Error watching for changes: {}", source)
+      }
+    }
+  }
+}
+
+impl std::error::Error for Error {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      Self::Config { source, .. } => Some(source),
+      Self::Glob { source, .. } => Some(source),
+      Self::Io { source, .. } | Self::Server { source, .. } => Some(source),
+      Self::Json(source) => Some(source),
+      Self::ThreadPool(source) => Some(source),
+      Self::Watch(source) => Some(source),
+      // The source of template errors is already part of the message.
+      _ => None,
+    }
+  }
+}
+
+impl From<serde_json::Error> for Error {
+  fn from(err: serde_json::Error) -> Self {
+    Self::Json(err)
+  }
+}
+
+impl From<rayon::ThreadPoolBuildError> for Error {
+  fn from(err: rayon::ThreadPoolBuildError) -> Self {
+    Self::ThreadPool(err)
+  }
+}
+
+impl From<notify::Error> for Error {
+  fn from(err: notify::Error) -> Self {
+    Self::Watch(err)
+  }
+}
+
+/// Adds the path to [`io::Error`]s, turning them into [`Error::Io`].
+pub(crate) trait IoResultExt<T> {
+  /// Turns the error into an [`Error::Io`] for `path`.
+  fn at<P: Into<PathBuf>>(self, path: P) -> Result<T>;
+}
+
+impl<T> IoResultExt<T> for io::Result<T> {
+  fn at<P: Into<PathBuf>>(self, path: P) -> Result<T> {
+    self.map_err(|source| Error::Io {
+      path: path.into(),
+      source,
+    })
+  }
+}
@@ -0,0 +1,245 @@
+use std::{
+  collections::BTreeMap, fs::metadata, path::PathBuf, time::UNIX_EPOCH,
+};
+
+use chrono::{TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+  document::{Date, Document},
+  launchpad::GeneratedPage,
+  section::{Section, SortBy},
+  Launchpad,
+};
+
+/// The file name of Atom feeds and of the template they're rendered with.
+pub const ATOM_FILE_NAME: &str = "atom.xml";
+
+/// The file name of RSS feeds and of the template they're rendered with.
+pub const RSS_FILE_NAME: &str = "rss.xml";
+
+/// The built-in template for Atom feeds.
+pub(crate) const ATOM_TEMPLATE: &str = include_str!("feed/atom.xml");
+
+/// The built-in template for RSS feeds.
+pub(crate) const RSS_TEMPLATE: &str = include_str!("feed/rss.xml");
+
+/// Feed settings for [`Launchpad`].
+///
+/// Feeds list the [`Document`]s that have a
+/// [`Metadata::date`](crate::document::Metadata::date), newest first. A feed
+/// for the whole site is written to the root of [`Launchpad::output`] as
+/// `atom.xml` and/or `rss.xml`, and optionally one next to the index of every
+/// [`Section`] and every term of a
+/// [`Taxonomy`](crate::taxonomy::Taxonomy).
+///
+/// Feeds are rendered with built-in templates, which can be replaced by
+/// putting an `atom.xml` or `rss.xml` in
+/// [`Templates::directory`](crate::launchpad::Templates::directory). The
+/// templates receive `feed`, which has the following fields:
+///
+/// * `entries`: the [`Document`]s in the feed.
+/// * `permalink` and `url`: the same as for [`Document`]s, for the page the
+///   feed belongs to.
+/// * `feed_permalink` and `feed_url`: the same, for the feed itself.
+/// * `title`: the title of the feed.
+/// * `updated`: the latest date of all the entries, using
+///   [`Metadata::updated`](crate::document::Metadata::updated) when it's set.
+///   When none of the entries have a date it's the last time one of the
+///   listed documents was modified, or the Unix epoch when there are none, so
+///   building the same source always gives the same feed.
+///
+/// Feed readers need absolute URLs, so [`Launchpad::base_url`] should include
+/// the domain when feeds are enabled.
+///
+/// Templates ending in `.xml` aren't autoescaped, since Tera's escaping is
+/// meant for HTML and would also escape the slashes in URLs. Use Tera's
+/// `escape_xml` filter on the values in them instead, like the built-in
+/// templates do.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[non_exhaustive]
+#[serde(default)]
+pub struct Feeds {
+  /// If set to `true`, Atom feeds are generated.
+  ///
+  /// Defaults to `false`.
+  pub atom: bool,
+  /// The maximum number of [`Document`]s in a feed.
+  ///
+  /// Defaults to `None`, which includes every document.
+  pub limit: Option<usize>,
+  /// If set to `true`, RSS 2.0 feeds are generated.
+  ///
+  /// Defaults to `false`.
+  pub rss: bool,
+  /// If set to `true`, every [`Section`] gets its own feeds with the
+  /// documents directly inside of it.
+  ///
+  /// Defaults to `false`.
+  pub sections: bool,
+  /// If set to `true`, every term of every
+  /// [`Taxonomy`](crate::taxonomy::Taxonomy) gets its own feeds with the
+  /// documents that list it.
+  ///
+  /// Defaults to `false`.
+  pub taxonomies: bool,
+  /// The title of the feed for the whole site. Section feeds use the title of
+  /// the section and taxonomy feeds the name of the term.
+  ///
+  /// Defaults to `None`, which leaves the title empty.
+  pub title: Option<String>,
+}
+
+impl Feeds {
+  /// Returns the file names of the enabled feed formats.
+  fn file_names(&self) -> Vec<&'static str> {
+    let mut names = vec![];
+    if self.atom {
+      names.push(ATOM_FILE_NAME);
+    }
+
+    if self.rss {
+      names.push(RSS_FILE_NAME);
+    }
+
+    names
+  }
+}
+
+/// A feed as templates receive it, see [`Feeds`] for details.
+#[derive(Serialize)]
+struct FeedContext<'a> {
+  entries: Vec<&'a Document>,
+  feed_permalink: String,
+  feed_url: String,
+  permalink: &'a str,
+  title: Option<&'a str>,
+  updated: Date,
+  url: String,
+}
+
+impl Launchpad {
+  /// Creates the feeds configured in [`Launchpad::feeds`].
+  pub(crate) fn feed_pages(
+    &self,
+    sections: &BTreeMap<PathBuf, Section>,
+    documents: &[Document],
+  ) -> Vec<GeneratedPage> {
+    let everything = (0..documents.len()).collect::<Vec<_>>();
+    let mut pages =
+      self.feeds_for(documents, &everything, "/", self.feeds.title.as_deref());
+
+    if self.feeds.sections {
+      // The root section is already covered by the feed for the whole site.
+      for section in sections
+        .values()
+        .filter(|section| !section.path.as_os_str().is_empty())
+      {
+        pages.extend(self.feeds_for(
+          documents,
+          &section.pages,
+          &section.permalink(documents),
+          section.title(documents),
+        ));
+      }
+    }
+
+    if self.feeds.taxonomies {
+      for taxonomy in &self.taxonomies {
+        for term in taxonomy.terms(documents) {
+          pages.extend(self.feeds_for(
+            documents,
+            &term.pages,
+            &taxonomy.term_permalink(&term),
+            Some(&term.name),
+          ));
+        }
+      }
+    }
+
+    pages
+  }
+
+  /// Creates the feeds in every enabled format for the page with the
+  /// root-relative `permalink`, with the [`Document`]s at the positions in
+  /// `items` that have a date.
+  fn feeds_for(
+    &self,
+    documents: &[Document],
+    items: &[usize],
+    permalink: &str,
+    title: Option<&str>,
+  ) -> Vec<GeneratedPage> {
+    let mut entries = items
+      .iter()
+      .map(|item| &documents[*item])
+      .filter(|document| document.metadata.date.is_some())
+      .collect::<Vec<_>>();
+    entries.sort_by(|a, b| SortBy::Date.compare(a, b));
+    if let Some(limit) = self.feeds.limit {
+      entries.truncate(limit);
+    }
+
+    let updated = entries
+      .iter()
+      .filter_map(|entry| entry.metadata.updated.or(entry.metadata.date))
+      .max()
+      .unwrap_or_else(|| last_modified(documents, items));
+
+    self
+      .feeds
+      .file_names()
+      .into_iter()
+      .map(|file_name| {
+        let feed_permalink = feed_permalink(permalink, file_name);
+        let output_path = PathBuf::from(feed_permalink.trim_start_matches('/'));
+        let feed = FeedContext {
+          entries: entries.clone(),
+          feed_url: self.absolute_url(&feed_permalink),
+          feed_permalink,
+          permalink,
+          title,
+          updated,
+          url: self.absolute_url(permalink),
+        };
+
+        GeneratedPage {
+          context: json!({ "feed": feed }),
+          output_path,
+          template: file_name.to_string(),
+        }
+      })
+      .collect()
+  }
+}
+
+/// Returns the URL of the feed named `file_name` relative to the root of the
+/// site, for a page with the root-relative `permalink`. Feeds are in the
+/// directory of the `permalink`.
+fn feed_permalink(permalink: &str, file_name: &str) -> String {
+  let directory = match permalink.rfind('/') {
+    Some(end) => &permalink[..=end],
+    None => "/",
+  };
+  format!("{}{}", directory, file_name)
+}
+
+/// Returns the last time any of the [`Document`]s at the positions in `items`
+/// had its source file modified, in whole seconds. When none of the files can
+/// be read this is the Unix epoch.
+fn last_modified(documents: &[Document], items: &[usize]) -> Date {
+  let modified = items
+    .iter()
+    .filter_map(|item| metadata(&documents[*item].source_path).ok())
+    .filter_map(|metadata| metadata.modified().ok())
+    .max()
+    .unwrap_or(UNIX_EPOCH);
+  let seconds = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+  Date(
+    Utc
+      .timestamp_opt(seconds.as_secs() as i64, 0)
+      .unwrap()
+      .into(),
+  )
+}
@@ -1,38 +1,109 @@
 use std::{
+  collections::HashMap,
   env::current_dir,
   ffi::OsStr,
-  fs::{copy, create_dir_all, remove_dir_all, write},
-  path::PathBuf,
+  fs::{
+    copy, create_dir_all, read_to_string, remove_dir_all, remove_file, write,
+  },
+  path::{Path, PathBuf},
 };
 
-use anyhow::{anyhow, Result};
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
 use serde::{Deserialize, Serialize};
-use tera::{Context, Tera};
+use tera::{Context, Tera, Value};
 use tracing::{debug, instrument, trace};
 use walkdir::WalkDir;
 
 use crate::{
+  cache::{self, BuildCache, CachedDocument, CACHE_FILE_NAME},
   document::Document,
-  markdown::render_markdown,
-  templating::{tera_highlight_code, tera_json_dump, tera_render_markdown},
+  error::{Error, IoResultExt, Result},
+  feed::Feeds,
+  markdown::{
+    render_markdown_parts, Admonitions, MarkdownOptions, MissingLink,
+    SourcePermalinks,
+  },
+  search::{SearchField, SearchIndex},
+  section::{section_contexts, Section},
+  shortcode::render_shortcodes,
+  taxonomy::Taxonomy,
+  templating::{
+    builtin_templates, tera_absolute_url, tera_highlight_code, tera_json_dump,
+    tera_render_markdown, tera_taxonomy_url, tera_url_for,
+  },
+  url,
 };
 
+/// The default file name of a Takeoff configuration file. See
+/// [`Launchpad::from_config_file`] for details.
+pub const CONFIG_FILE_NAME: &str = "takeoff.toml";
+
 /// The entry point for Takeoff.
 #[derive(Debug, Deserialize, Serialize)]
 #[non_exhaustive]
+#[serde(default)]
 pub struct Launchpad {
+  /// Admonition settings, see [`Admonitions`] for details.
+  ///
+  /// Defaults to rendering blockquotes with a marker like `[!NOTE]` as
+  /// regular blockquotes.
+  pub admonitions: Admonitions,
+  /// The URL the site will be deployed at, like `"https://example.com/"` or
+  /// `"https://example.com/docs/v2/"`. When only a path like `"/docs/v2/"` is
+  /// given, the site can still be deployed under a sub-path but absolute URLs
+  /// can't be made.
+  ///
+  /// Links in Markdown that start with a `/` get the path of the base URL put
+  /// in front of them, and templates can do the same with the `url_for` and
+  /// `absolute_url` functions. See [`Launchpad::url`] and
+  /// [`Launchpad::absolute_url`] for details.
+  ///
+  /// Defaults to `"/"`.
+  pub base_url: String,
   /// If set to `true`, any `.scss` files found in [`Launchpad::statics`]
   /// will be compiled and output as CSS with [`grass`]. Sass partials
   /// (`.scss` files starting with an underscore) will also be skipped.
   ///
   /// Defaults to `false`.
   pub compile_sass: bool,
+  /// If set to `true`, [`Launchpad::take_off`] fails with
+  /// [`Error::BrokenLinks`] when [`Launchpad::check_links`] finds any broken
+  /// links after generating the site. This only applies in
+  /// [`Mode::Production`].
+  ///
+  /// Defaults to `false`.
+  pub deny_broken_links: bool,
+  /// Feed settings, see [`Feeds`] for details.
+  ///
+  /// Defaults to generating no feeds.
+  pub feeds: Feeds,
+  /// If set to `true`, every heading in the Markdown of [`Document`]s gets a
+  /// link to itself added to the end of it. Headings always get an `id`, see
+  /// [`Heading::id`](crate::markdown::Heading::id).
+  ///
+  /// Defaults to `false`.
+  pub heading_anchors: bool,
+  /// If set to `true`, a [`BuildCache`] is kept in [`Launchpad::output`] and
+  /// only outputs whose inputs have changed since the previous build are
+  /// generated again, everything else is left untouched.
+  ///
+  /// The inputs of a [`Document`] are its Markdown, its template and all the
+  /// templates that one depends on, plus the Launchpad itself and the metadata
+  /// and permalinks of every document. When one of those templates uses the
+  /// `content` or `toc` of *other* documents, like through `paginator.pages`
+  /// or `documents`, the content of every document is an input as well.
+  ///
+  /// This only applies in [`Mode::Development`], since [`Mode::Production`]
+  /// always does a complete rebuild.
+  ///
+  /// Defaults to `false`.
+  pub incremental: bool,
   /// The Launchpad mode.
   ///
   /// * [`Mode::Development`] will render all [`Document`]s.
   /// * [`Mode::Production`] only renders [`Document`]s with `is_draft = false`
-  /// and will trigger a complete rebuild of the site (completely removing
-  /// `output` before starting).
+  ///   and will trigger a complete rebuild of the site (completely removing
+  ///   `output` before starting).
   ///
   /// Defaults to [`Mode::Development`]
   pub mode: Mode,
@@ -40,6 +111,41 @@ pub struct Launchpad {
   ///
   /// Defaults to `"public"`.
   pub output: PathBuf,
+  /// If set to `true`, [`Document`]s are written as `index.html` inside a
+  /// directory named after them, so `about.md` becomes `about/index.html` and
+  /// can be linked to as `/about/`. Documents named `index.md` are always the
+  /// index of their directory.
+  ///
+  /// Individual documents can override this with
+  /// [`Metadata::pretty_url`](crate::document::Metadata::pretty_url).
+  ///
+  /// Defaults to `false`.
+  pub pretty_urls: bool,
+  /// If set to `true`, a `robots.txt` that allows everything is written to
+  /// the root of [`Launchpad::output`], which points to the sitemap when
+  /// [`Launchpad::sitemap`] is enabled.
+  ///
+  /// It's rendered with a built-in template that can be replaced by putting
+  /// a `robots.txt` in [`Templates::directory`], which receives the absolute
+  /// URL of the sitemap as `sitemap_url`.
+  ///
+  /// Defaults to `false`.
+  pub robots_txt: bool,
+  /// Search index settings, see [`SearchIndex`] for details.
+  ///
+  /// Defaults to generating no search index.
+  pub search_index: SearchIndex,
+  /// If set to `true`, a `sitemap.xml` listing every rendered [`Document`] is
+  /// written to the root of [`Launchpad::output`], see
+  /// [`SitemapEntry`](crate::sitemap::SitemapEntry) for details.
+  ///
+  /// It's rendered with a built-in template that can be replaced by putting
+  /// a `sitemap.xml` in [`Templates::directory`], which receives `sitemap`
+  /// with its `entries` and `url`. Search engines need absolute URLs, so
+  /// [`Launchpad::base_url`] should include the domain.
+  ///
+  /// Defaults to `false`.
+  pub sitemap: bool,
   /// The root directory to look for Markdown and static files.
   ///
   /// Defaults to `"source"`.
@@ -55,11 +161,46 @@ pub struct Launchpad {
   /// \* Optionally, some files can be automatically processed as well, see
   /// [`Launchpad::compile_sass`].
   pub statics: Vec<(String, String)>,
+  /// The taxonomies to group [`Document`]s by, see [`Taxonomy`] for details.
+  ///
+  /// Defaults to none.
+  pub taxonomies: Vec<Taxonomy>,
   /// Templating settings.
   pub templates: Templates,
   /// The [`tera`] instance to re-use for templating.
   #[serde(skip)]
   pub tera: Tera,
+  /// The number of threads to parse and render [`Document`]s with. The output
+  /// is the same regardless of how many are used.
+  ///
+  /// Defaults to `0`, which uses one thread per CPU.
+  pub threads: usize,
+}
+
+impl Default for Launchpad {
+  fn default() -> Self {
+    Self {
+      admonitions: Admonitions::default(),
+      base_url: "/".to_string(),
+      compile_sass: false,
+      deny_broken_links: false,
+      feeds: Feeds::default(),
+      heading_anchors: false,
+      incremental: false,
+      mode: Mode::Development,
+      output: "public".into(),
+      pretty_urls: false,
+      robots_txt: false,
+      search_index: SearchIndex::default(),
+      sitemap: false,
+      source: "source".into(),
+      statics: vec![],
+      taxonomies: vec![],
+      templates: Templates::default(),
+      tera: Tera::default(),
+      threads: 0,
+    }
+  }
 }
 
 impl Launchpad {
@@ -68,86 +209,451 @@ impl Launchpad {
     LaunchpadBuilder::default()
   }
 
+  /// Reads a TOML configuration file (usually [`CONFIG_FILE_NAME`]) and
+  /// returns the resulting [`Launchpad`]. See [`LaunchpadBuilder::from_toml`]
+  /// for details on the format.
+  #[instrument]
+  pub fn from_config_file(path: &Path) -> Result<Self> {
+    LaunchpadBuilder::from_config_file(path)?.build()
+  }
+
   /// Parses and returns all [`Document`]s defined by this [`Launchpad`].
   #[instrument(skip(self))]
   pub fn parse_documents(&self) -> Result<Vec<Document>> {
-    let mut documents = vec![];
+    self.parse_documents_cached(&mut BuildCache::default())
+  }
+
+  /// The same as [`Launchpad::parse_documents`] but re-uses the rendered
+  /// Markdown from the [`BuildCache`] for documents that haven't changed, and
+  /// updates it for the ones that have.
+  #[instrument(skip(self, cache))]
+  pub fn parse_documents_cached(
+    &self,
+    cache: &mut BuildCache,
+  ) -> Result<Vec<Document>> {
+    self
+      .thread_pool()?
+      .install(|| self.collect_documents(cache))
+  }
 
+  /// Parses and renders the [`Document`]s in parallel on the current
+  /// [`rayon`] thread pool. All of them are parsed before any is rendered, so
+  /// links to `.md` files can be resolved to their permalinks.
+  fn collect_documents(&self, cache: &mut BuildCache) -> Result<Vec<Document>> {
     debug!("Walking {:?}", self.source);
-    for entry in WalkDir::new(&self.source)
+    let paths = WalkDir::new(&self.source)
       .follow_links(true)
+      .sort_by(|a, b| a.file_name().cmp(b.file_name()))
       .into_iter()
-      .filter_map(Result::ok)
-    {
-      let path = entry.path();
-      if path.extension().and_then(OsStr::to_str) == Some("md") {
-        trace!("Parsing {:?}", path);
-        let mut document = Document::new(path.into())?;
-        document.content = render_markdown(&document.content);
-        documents.push(document);
-      }
-    }
+      .filter_map(|entry| entry.ok())
+      .map(|entry| entry.into_path())
+      .filter(|path| path.extension().and_then(OsStr::to_str) == Some("md"))
+      .collect::<Vec<_>>();
 
     debug!("Launchpad mode: {:?}", self.mode);
     if self.mode == Mode::Production {
       debug!("Excluding documents where is_draft = true");
     }
 
-    let documents = documents.into_iter().filter(|doc| {
-      if self.mode == Mode::Production {
-        trace!("Excluding {:?}", doc.source_path);
-        !doc.metadata.is_draft
+    let results = paths
+      .into_par_iter()
+      .map(|path| {
+        trace!("Parsing {:?}", path);
+        let mut document = Document::new(path)?;
+        if document.metadata.ignore {
+          trace!("Ignoring {:?}", document.source_path);
+          return Ok(None);
+        }
+
+        if self.mode == Mode::Production && document.metadata.is_draft {
+          trace!("Excluding {:?}", document.source_path);
+          return Ok(None);
+        }
+
+        let destination = document.destination(self)?;
+        document.output_path = self.output_key(&destination)?;
+        document.permalink = permalink(&document.output_path);
+        document.section = document.source_directory(self)?.to_path_buf();
+        document.url = self.url(&document.permalink);
+
+        Ok(Some(document))
+      })
+      .collect::<Vec<_>>();
+    let documents = collect_results(results)?
+      .into_iter()
+      .flatten()
+      .collect::<Vec<_>>();
+    check_output_paths(&documents)?;
+
+    let permalinks = SourcePermalinks::new(&self.source, &documents);
+    // Which documents exist and where they end up decides what links to `.md`
+    // files become, so any change to that has to render the Markdown again.
+    let permalinks_hash = cache::hash(
+      &documents
+        .iter()
+        .map(|document| (&document.source_path, &document.permalink))
+        .collect::<Vec<_>>(),
+    );
+
+    let results = documents
+      .into_par_iter()
+      .map(|document| {
+        self.render_document(document, &permalinks, permalinks_hash, cache)
+      })
+      .collect::<Vec<_>>();
+
+    let mut documents = vec![];
+    for (document, rendered) in collect_results(results)? {
+      if let Some(rendered) = rendered {
+        cache
+          .documents
+          .insert(document.source_path.clone(), rendered);
+      }
+
+      documents.push(document);
+    }
+
+    Ok(documents)
+  }
+
+  /// Renders the Markdown of a [`Document`], or re-uses it from the
+  /// [`BuildCache`] if it hasn't changed. When it was rendered, the new
+  /// [`CachedDocument`] is returned alongside it.
+  ///
+  /// Shortcodes are rendered first, see [`render_shortcodes`]. Links to `.md`
+  /// files that aren't a [`Document`] result in an [`Error::MissingDocument`]
+  /// for each of them.
+  fn render_document(
+    &self,
+    mut document: Document,
+    permalinks: &SourcePermalinks,
+    permalinks_hash: u64,
+    cache: &BuildCache,
+  ) -> Result<(Document, Option<CachedDocument>)> {
+    let markdown = render_shortcodes(&document.content, &self.tera)
+      .map_err(|err| shortcode_error(&document, err))?;
+
+    // Links in the Markdown are rewritten for the base URL, blockquotes can
+    // become admonitions and headings can get anchors, so those have to be
+    // part of the hash as well.
+    let markdown_hash = cache::hash(&(
+      &markdown,
+      &self.admonitions,
+      &self.base_url,
+      self.heading_anchors,
+      permalinks_hash,
+    ));
+    match cache.documents.get(&document.source_path) {
+      Some(cached) if cached.hash == markdown_hash => {
+        trace!("Re-using Markdown for {:?}", document.source_path);
+        document.content = cached.content.clone();
+        document.links = cached.links.clone();
+        document.text = cached.text.clone();
+        document.toc = cached.toc.clone();
+        Ok((document, None))
+      }
+      _ => {
+        let options = MarkdownOptions {
+          admonitions: self.admonitions.enabled.then_some(&self.admonitions),
+          base_url: &self.base_url,
+          heading_anchors: self.heading_anchors,
+          path: Some(&document.source_path),
+          permalinks: Some(permalinks),
+        };
+        let rendered = render_markdown_parts(&markdown, &options);
+        if !rendered.missing_links.is_empty() {
+          let errors = rendered
+            .missing_links
+            .iter()
+            .map(|link| Err(missing_document(&document, link)))
+            .collect::<Vec<Result<()>>>();
+          collect_results(errors)?;
+        }
+
+        document.content = rendered.html;
+        document.links = rendered.links;
+        document.text = rendered.text;
+        document.toc = rendered.toc;
+        let rendered = CachedDocument {
+          content: document.content.clone(),
+          hash: markdown_hash,
+          links: document.links.clone(),
+          text: document.text.clone(),
+          toc: document.toc.clone(),
+        };
+        Ok((document, Some(rendered)))
+      }
+    }
+  }
+
+  /// Creates the [`rayon`] thread pool used for parsing and rendering, with
+  /// [`Launchpad::threads`] workers.
+  fn thread_pool(&self) -> Result<ThreadPool> {
+    ThreadPoolBuilder::new()
+      .num_threads(self.threads)
+      .build()
+      .map_err(Into::into)
+  }
+
+  /// Returns the template to render a [`Document`] with, which is its
+  /// [`Metadata::template`](crate::document::Metadata::template) or otherwise
+  /// [`Templates::default`].
+  pub(crate) fn template<'a>(&'a self, document: &'a Document) -> &'a str {
+    document
+      .metadata
+      .template
+      .as_deref()
+      .unwrap_or(&self.templates.default)
+  }
+
+  /// Returns the path of an output file relative to [`Launchpad::output`].
+  fn output_key(&self, path: &Path) -> Result<PathBuf> {
+    path
+      .strip_prefix(&self.output)
+      .map(Path::to_path_buf)
+      .map_err(|_| Error::OutsideDirectory {
+        directory: self.output.clone(),
+        path: path.to_path_buf(),
+      })
+  }
+
+  /// Returns the root-relative URL for `path`, a path relative to the root of
+  /// the site, by putting the path of [`Launchpad::base_url`] in front of it.
+  /// See [`url::relative_url`] for details.
+  pub fn url(&self, path: &str) -> String {
+    url::relative_url(&self.base_url, path)
+  }
+
+  /// Returns the absolute URL for `path`, a path relative to the root of the
+  /// site, by putting [`Launchpad::base_url`] in front of it. See
+  /// [`url::absolute_url`] for details.
+  pub fn absolute_url(&self, path: &str) -> String {
+    url::absolute_url(&self.base_url, path)
+  }
+
+  /// Renders the [`GeneratedPage`]s whose inputs have changed since the
+  /// previous build, see [`Launchpad::incremental`] for details.
+  fn render_generated_pages(
+    &self,
+    pages: Vec<GeneratedPage>,
+    context: &Context,
+    cache: &mut BuildCache,
+    outputs: &mut HashMap<PathBuf, u64>,
+    global_fingerprint: u64,
+    content_fingerprint: u64,
+  ) -> Result<()> {
+    let templates_directory = self.source.join(&self.templates.directory);
+
+    let mut stale = vec![];
+    for page in pages {
+      let destination = self.output.join(&page.output_path);
+      let fingerprint = cache::hash(&(
+        global_fingerprint,
+        page.context.to_string(),
+        cache.template_fingerprint(&templates_directory, &page.template),
+        cache
+          .uses_other_content(&templates_directory, &page.template)
+          .then_some(content_fingerprint),
+      ));
+
+      let fresh = cache.is_fresh(&page.output_path, fingerprint, &destination);
+      outputs.insert(page.output_path.clone(), fingerprint);
+      if fresh {
+        trace!("Skipping unchanged {:?}", page.output_path);
       } else {
-        true
+        stale.push((page, destination));
       }
-    });
+    }
+
+    let results = stale
+      .into_par_iter()
+      .map(|(page, destination)| {
+        debug!("Rendering {:?}", page.output_path);
+        let template_error = |source| Error::Template {
+          document: None,
+          name: Some(page.template.clone()),
+          source,
+        };
+
+        let mut page_context = context.clone();
+        page_context.extend(
+          Context::from_value(page.context.clone()).map_err(template_error)?,
+        );
 
-    Ok(documents.collect())
+        trace!("Using template: {}", page.template);
+        let html = self
+          .tera
+          .render(&page.template, &page_context)
+          .map_err(template_error)?;
+
+        let directory = destination.parent().unwrap();
+        create_dir_all(directory).at(directory)?;
+        trace!("Writing to {:?}", destination);
+        write(&destination, &html).at(destination)
+      })
+      .collect::<Vec<_>>();
+    collect_results(results)?;
+
+    Ok(())
   }
 
-  /// Generates the site defined by this [`Launchpad`].
+  /// Generates the site defined by this [`Launchpad`], returning the
+  /// [`Document`]s it was made from so they can be passed to
+  /// [`Launchpad::check_links`].
   #[instrument(skip(self))]
-  pub fn take_off(&self) -> Result<()> {
+  pub fn take_off(&self) -> Result<Vec<Document>> {
+    self.thread_pool()?.install(|| self.launch())
+  }
+
+  /// Does the actual work for [`Launchpad::take_off`] on the current
+  /// [`rayon`] thread pool.
+  fn launch(&self) -> Result<Vec<Document>> {
     if self.mode == Mode::Production && self.output.exists() {
       debug!("Removing {:?}", self.output);
-      remove_dir_all(&self.output)?;
+      remove_dir_all(&self.output).at(&self.output)?;
     }
 
-    let documents = self.parse_documents()?;
+    let incremental = self.incremental && self.mode == Mode::Development;
+    let cache_path = self.output.join(CACHE_FILE_NAME);
+    let mut cache = if incremental {
+      BuildCache::load(&cache_path)
+    } else {
+      BuildCache::default()
+    };
+
+    // The fingerprints of every output generated in this build, which will
+    // replace the ones from the previous build.
+    let mut outputs = HashMap::new();
+
+    let documents = self.collect_documents(&mut cache)?;
+
+    // Every template has access to the Launchpad and the metadata of all
+    // documents through listings like `section.pages`, so when any of those
+    // change every page is re-rendered.
+    let global_fingerprint = cache::hash(&serde_json::to_string(&(
+      self,
+      documents
+        .iter()
+        .map(|doc| (&doc.source_path, &doc.metadata, &doc.permalink))
+        .collect::<Vec<_>>(),
+    ))?);
+
+    // Only pages whose templates use the content of other documents depend on
+    // all of it, see `BuildCache::uses_other_content`.
+    let content_fingerprint = cache::hash(
+      &documents.iter().map(|doc| &doc.content).collect::<Vec<_>>(),
+    );
+
+    let templates_directory = self.source.join(&self.templates.directory);
+
+    // Figure out which documents need to be rendered first, since the cache
+    // can't be updated from multiple threads.
+    let mut stale = vec![];
+    for (position, document) in documents.iter().enumerate() {
+      let template = self.template(document);
+
+      let destination = document.create_destination(self)?;
+      let fingerprint = cache::hash(&(
+        global_fingerprint,
+        &document.content,
+        cache.template_fingerprint(&templates_directory, template),
+        cache
+          .uses_other_content(&templates_directory, template)
+          .then_some(content_fingerprint),
+      ));
+
+      let key = self.output_key(&destination)?;
+      let fresh = cache.is_fresh(&key, fingerprint, &destination);
+      outputs.insert(key, fingerprint);
+      if fresh {
+        trace!("Skipping unchanged {:?}", document.source_path);
+      } else {
+        stale.push((position, document, template, destination));
+      }
+    }
+
+    let sections = Section::from_documents(&documents);
+    let section_contexts = section_contexts(self, &sections, &documents)?;
+    let (paginators, mut pages) =
+      self.paginate_sections(&sections, &section_contexts, &documents)?;
 
     let mut context = Context::new();
     context.insert("launchpad", self);
     context.insert("documents", &documents);
 
-    for document in documents {
-      debug!("Rendering {:?}", document.source_path);
+    for taxonomy in &self.taxonomies {
+      pages.extend(taxonomy.pages(self, &documents));
+    }
+    pages.extend(self.feed_pages(&sections, &documents));
+    pages.extend(self.sitemap_pages(&documents));
+    check_generated_output_paths(&documents, &pages)?;
 
-      context.insert("document", &document);
-      context.insert("metadata", &document.metadata);
-      context.insert("extra", &document.metadata.extra);
+    let results = stale
+      .into_par_iter()
+      .map_init(
+        || context.clone(),
+        |context, (position, document, template, destination)| {
+          debug!("Rendering {:?}", document.source_path);
 
-      let template = document
-        .metadata
-        .template
-        .as_ref()
-        .unwrap_or(&self.templates.default);
+          let section = &sections[&document.section];
+          let (previous, next) = section.previous_and_next(position);
+          context.insert("document", &document);
+          context.insert("section", &section_contexts[&document.section]);
+          context.insert("previous", &previous.map(|page| &documents[page]));
+          context.insert("next", &next.map(|page| &documents[page]));
+          match paginators.get(&document.section) {
+            Some(paginator) if document.is_section_index() => {
+              context.insert("paginator", paginator)
+            }
+            // The context is re-used between documents, so remove the
+            // paginator of the previous one.
+            _ => {
+              context.remove("paginator");
+            }
+          }
+          context.insert("metadata", &document.metadata);
+          context.insert("extra", &document.metadata.extra);
 
-      trace!("Using template: {}", template);
-      let html = self.tera.render(&template, &context)?;
+          trace!("Using template: {}", template);
+          let html = self.tera.render(template, context).map_err(|source| {
+            Error::Template {
+              document: Some(document.source_path.clone()),
+              name: Some(template.to_string()),
+              source,
+            }
+          })?;
 
-      let destination = document.create_destination(self)?;
-      trace!("Writing to {:?}", destination);
-      write(destination, &html)?;
-    }
+          trace!("Writing to {:?}", destination);
+          write(&destination, &html).at(destination)
+        },
+      )
+      .collect::<Vec<_>>();
+    collect_results(results)?;
+
+    self.render_generated_pages(
+      pages,
+      &context,
+      &mut cache,
+      &mut outputs,
+      global_fingerprint,
+      content_fingerprint,
+    )?;
+    self.write_search_index(&documents, &cache, &mut outputs)?;
 
     for (source, destination) in &self.statics {
       let source = self.source.join(source);
       let destination = self.output.join(destination);
-      create_dir_all(&destination)?;
+      create_dir_all(&destination).at(&destination)?;
       debug!("Processing ({:?},{:?})", source, destination);
 
-      for entry in glob::glob(source.to_str().unwrap())?
-        .filter_map(Result::ok)
+      let pattern = source.to_str().unwrap();
+      let entries = glob::glob(pattern).map_err(|source| Error::Glob {
+        pattern: pattern.to_string(),
+        source,
+      })?;
+
+      for entry in entries
+        .filter_map(|entry| entry.ok())
         .filter(|e| e.is_file())
       {
         let entry_destination = destination.join(entry.file_name().unwrap());
@@ -156,40 +662,244 @@ impl Launchpad {
           .and_then(OsStr::to_str)
           .unwrap();
 
-        if self.compile_sass && file_name.ends_with(".scss") {
-          // Don't attempt to compile Sass partials.
-          if file_name.starts_with('_') {
-            trace!("Skipping Sass partial {:?}", entry);
-            continue;
-          }
+        let compile = self.compile_sass && file_name.ends_with(".scss");
+
+        // Don't attempt to compile Sass partials.
+        if compile && file_name.starts_with('_') {
+          trace!("Skipping Sass partial {:?}", entry);
+          continue;
+        }
+
+        let entry_destination = if compile {
+          entry_destination.with_extension("css")
+        } else {
+          entry_destination
+        };
+
+        let fingerprint = if compile {
+          sass_fingerprint(&entry)
+        } else {
+          cache::hash_file(&entry)
+        };
+
+        let key = self.output_key(&entry_destination)?;
+        let fresh = cache.is_fresh(&key, fingerprint, &entry_destination);
+        outputs.insert(key, fingerprint);
+        if fresh {
+          trace!("Skipping unchanged {:?}", entry);
+          continue;
+        }
 
+        if compile {
           trace!("Compiling Sass for {:?}", entry);
           let css = grass::from_path(
-            &entry.as_path().to_str().unwrap(),
+            entry.as_path().to_str().unwrap(),
             &grass::Options::default(),
           )
-          .map_err(|err| anyhow!(err.to_string()))?;
+          .map_err(|err| Error::Sass {
+            message: err.to_string(),
+            path: entry.clone(),
+          })?;
 
-          let entry_destination = entry_destination.with_extension("css");
           trace!("Writing Sass to {:?}", entry_destination);
-          write(entry_destination, css)?;
+          write(&entry_destination, css).at(entry_destination)?;
         } else {
           trace!("Copying {:?} to {:?}", entry, entry_destination);
-          copy(entry, entry_destination)?;
+          copy(&entry, &entry_destination).at(entry_destination)?;
         }
       }
     }
 
-    Ok(())
+    if incremental {
+      // Remove outputs from the previous build that weren't generated again,
+      // like documents that have been deleted since.
+      for key in cache
+        .outputs
+        .keys()
+        .filter(|key| !outputs.contains_key(*key))
+      {
+        let stale = self.output.join(key);
+        if stale.is_file() {
+          debug!("Removing stale {:?}", stale);
+          remove_file(&stale).at(stale)?;
+        }
+      }
+
+      cache.outputs = outputs;
+      cache.save(&cache_path)?;
+    }
+
+    if self.deny_broken_links && self.mode == Mode::Production {
+      let broken = self.check_links(&documents)?;
+      if !broken.is_empty() {
+        return Err(Error::BrokenLinks(broken));
+      }
+    }
+
+    Ok(documents)
+  }
+}
+
+/// A page that is rendered from a template like a [`Document`], but that is
+/// generated by Takeoff instead, like the pages of a [`Taxonomy`].
+pub(crate) struct GeneratedPage {
+  /// The variables to add to the template context, as a JSON object.
+  pub context: Value,
+  /// The path to write the page to, relative to [`Launchpad::output`].
+  pub output_path: PathBuf,
+  /// The template to render the page with.
+  pub template: String,
+}
+
+/// Turns a list of results into a list of their values, or into an error. When
+/// more than one error happened they're all returned in [`Error::Multiple`].
+fn collect_results<T>(results: Vec<Result<T>>) -> Result<Vec<T>> {
+  let mut values = vec![];
+  let mut errors = vec![];
+  for result in results {
+    match result {
+      Ok(value) => values.push(value),
+      Err(err) => errors.push(err),
+    }
+  }
+
+  match errors.len() {
+    0 => Ok(values),
+    1 => Err(errors.remove(0)),
+    _ => Err(Error::Multiple(errors)),
+  }
+}
+
+/// Checks that no two [`Document`]s are written to the same
+/// [`Document::output_path`], which would make one silently overwrite the
+/// other. Every collision results in an [`Error::DuplicateOutput`].
+fn check_output_paths(documents: &[Document]) -> Result<()> {
+  let mut sources = HashMap::new();
+  let results = documents
+    .iter()
+    .map(|document| {
+      match sources.insert(&document.output_path, &document.source_path) {
+        Some(first) => Err(Error::DuplicateOutput {
+          first: first.clone(),
+          output_path: document.output_path.clone(),
+          second: document.source_path.clone(),
+        }),
+        None => Ok(()),
+      }
+    })
+    .collect();
+
+  collect_results(results).map(|_| ())
+}
+
+/// Checks that no [`GeneratedPage`] is written to the same output file as a
+/// [`Document`] or another generated page, which would make it silently
+/// overwrite that. Every collision results in an [`Error::GeneratedOutput`].
+fn check_generated_output_paths(
+  documents: &[Document],
+  pages: &[GeneratedPage],
+) -> Result<()> {
+  let mut sources = documents
+    .iter()
+    .map(|document| (&document.output_path, Some(&document.source_path)))
+    .collect::<HashMap<_, _>>();
+  let results = pages
+    .iter()
+    .map(|page| match sources.insert(&page.output_path, None) {
+      Some(document) => Err(Error::GeneratedOutput {
+        document: document.cloned(),
+        output_path: page.output_path.clone(),
+        template: page.template.clone(),
+      }),
+      None => Ok(()),
+    })
+    .collect();
+
+  collect_results(results).map(|_| ())
+}
+
+/// Creates the [`Error::MissingDocument`] for a link in a [`Document`], with
+/// the line in its Markdown file instead of the line after the front matter.
+fn missing_document(document: &Document, link: &MissingLink) -> Error {
+  Error::MissingDocument {
+    line: document.front_matter_lines + link.line,
+    path: document.source_path.clone(),
+    url: link.url.clone(),
   }
 }
 
+/// Adds the [`Document`] to an error from [`render_shortcodes`], with the line
+/// in its Markdown file instead of the line after the front matter.
+fn shortcode_error(document: &Document, err: Error) -> Error {
+  let path = document.source_path.clone();
+  match err {
+    Error::Shortcode { line, message, .. } => Error::Shortcode {
+      line: document.front_matter_lines + line,
+      message,
+      path: Some(path),
+    },
+    Error::Template { name, source, .. } => Error::Template {
+      document: Some(path),
+      name,
+      source,
+    },
+    err => err,
+  }
+}
+
+/// Returns the root-relative URL of an output file from its path relative to
+/// [`Launchpad::output`], leaving out the file name of `index.html` files so
+/// they link to their directory.
+fn permalink(output_path: &Path) -> String {
+  let is_index = output_path.file_name() == Some(OsStr::new("index.html"));
+
+  let mut permalink = String::new();
+  for component in output_path.iter() {
+    if is_index && component == "index.html" {
+      break;
+    }
+
+    permalink += "/";
+    permalink += &component.to_string_lossy();
+  }
+
+  if is_index {
+    permalink += "/";
+  }
+
+  permalink
+}
+
+/// Returns a fingerprint for a Sass file that also includes the partials in
+/// its directory and every directory below it, since any of those can be
+/// imported, including from other partials.
+fn sass_fingerprint(path: &Path) -> u64 {
+  let mut hashes = vec![cache::hash_file(path)];
+
+  let directory = path.parent().unwrap();
+  let mut partials = WalkDir::new(directory)
+    .into_iter()
+    .filter_map(|entry| entry.ok())
+    .map(|entry| entry.into_path())
+    .filter(|path| {
+      let file_name = path.file_name().and_then(OsStr::to_str).unwrap();
+      file_name.starts_with('_') && file_name.ends_with(".scss")
+    })
+    .collect::<Vec<_>>();
+
+  partials.sort();
+  hashes.extend(partials.iter().map(|partial| cache::hash_file(partial)));
+  cache::hash(&hashes)
+}
+
 /// The mode for [`Launchpad`] to take off in. See [`Launchpad::mode`] for
 /// details.
 #[derive(Debug, Deserialize, Serialize, PartialEq)]
 #[non_exhaustive]
 pub enum Mode {
+  #[serde(alias = "development")]
   Development,
+  #[serde(alias = "production")]
   Production,
 }
 
@@ -233,25 +943,140 @@ impl Templates {
 }
 
 /// A builder to configure a [`Launchpad`].
-#[derive(Debug)]
+#[derive(Debug, Default)]
 #[non_exhaustive]
 pub struct LaunchpadBuilder(pub(crate) Launchpad);
 
-impl Default for LaunchpadBuilder {
-  fn default() -> Self {
+impl LaunchpadBuilder {
+  /// Creates a new [`LaunchpadBuilder`] from a TOML string with [`serde`] and
+  /// [`toml`]. Every key is optional and falls back to the same default as
+  /// the builder, so further builder calls can override what the file sets.
+  ///
+  /// ```toml
+  /// base_url = "/"
+  /// compile_sass = true
+  /// deny_broken_links = true
+  /// heading_anchors = true
+  /// mode = "production"
+  /// output = "public"
+  /// pretty_urls = false
+  /// robots_txt = true
+  /// sitemap = true
+  /// source = "source"
+  /// statics = [["static/scss/*.scss", "css/"]]
+  ///
+  /// [admonitions]
+  /// class = "callout"
+  /// enabled = true
+  ///
+  /// [admonitions.titles]
+  /// note = "Note"
+  /// warning = "Watch out"
+  ///
+  /// [feeds]
+  /// atom = true
+  /// limit = 20
+  /// rss = false
+  /// sections = true
+  /// taxonomies = false
+  /// title = "My Site"
+  ///
+  /// [[taxonomies]]
+  /// name = "tags"
+  /// paginate_by = 10
+  /// sort_by = "date"
+  ///
+  /// [search_index]
+  /// enabled = true
+  /// fields = ["title", "url", "text"]
+  /// max_text_length = 5000
+  ///
+  /// [templates]
+  /// default = "base.html"
+  /// directory = "templates"
+  /// ```
+  #[instrument(skip(source))]
+  pub fn from_toml(source: &str) -> Result<Self> {
+    let launchpad: Launchpad = toml::from_str(source)
+      .map_err(|source| Error::Config { path: None, source })?;
+    trace!("Parsed configuration: {:?}", launchpad);
+    Ok(Self(launchpad))
+  }
+
+  /// Reads a TOML configuration file (usually [`CONFIG_FILE_NAME`]) like
+  /// [`LaunchpadBuilder::from_toml`] does. A relative
+  /// [`Launchpad::source`] and [`Launchpad::output`] are resolved against the
+  /// directory the file is in, so the site builds the same no matter where
+  /// it's built from.
+  #[instrument]
+  pub fn from_config_file(path: &Path) -> Result<Self> {
+    debug!("Reading configuration");
+    let source = read_to_string(path).at(path)?;
+    let mut launchpad: Launchpad =
+      toml::from_str(&source).map_err(|source| Error::Config {
+        path: Some(path.into()),
+        source,
+      })?;
+    trace!("Parsed configuration: {:?}", launchpad);
+
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    launchpad.output = directory.join(&launchpad.output);
+    launchpad.source = directory.join(&launchpad.source);
+    Ok(Self(launchpad))
+  }
+
+  /// Set [`Admonitions::enabled`].
+  #[instrument(skip(self))]
+  pub fn admonitions(self, enabled: bool) -> Self {
+    trace!("Setting admonitions.enabled to {}", enabled);
     Self(Launchpad {
-      compile_sass: false,
-      mode: Mode::Development,
-      output: "public".into(),
-      source: "source".into(),
-      statics: vec![],
-      templates: Templates::default(),
-      tera: Tera::default(),
+      admonitions: Admonitions {
+        enabled,
+        ..self.0.admonitions
+      },
+      ..self.0
+    })
+  }
+
+  /// Set [`Admonitions::class`].
+  #[instrument(skip(self))]
+  pub fn admonitions_class(self, class: &str) -> Self {
+    trace!("Setting admonitions.class to {}", class);
+    Self(Launchpad {
+      admonitions: Admonitions {
+        class: class.to_string(),
+        ..self.0.admonitions
+      },
+      ..self.0
+    })
+  }
+
+  /// Set [`Admonitions::titles`], as pairs of kinds and titles.
+  #[instrument(skip(self))]
+  pub fn admonitions_titles(self, titles: Vec<(&str, &str)>) -> Self {
+    trace!("Setting admonitions.titles to {:?}", titles);
+    Self(Launchpad {
+      admonitions: Admonitions {
+        titles: titles
+          .into_iter()
+          .map(|(kind, title)| (kind.to_lowercase(), title.to_string()))
+          .collect(),
+        ..self.0.admonitions
+      },
+      ..self.0
+    })
+  }
+
+  /// Set [`Launchpad::base_url`].
+  #[instrument(skip(self))]
+  pub fn base_url(self, base_url: &str) -> Self {
+    trace!("Setting base_url to {}", base_url);
+    Self(Launchpad {
+      base_url: base_url.to_string(),
+      ..self.0
     })
   }
-}
 
-impl LaunchpadBuilder {
   /// Set [`Launchpad::compile_sass`].
   #[instrument(skip(self))]
   pub fn compile_sass(self, compile_sass: bool) -> Self {
@@ -262,6 +1087,114 @@ impl LaunchpadBuilder {
     })
   }
 
+  /// Set [`Launchpad::deny_broken_links`].
+  #[instrument(skip(self))]
+  pub fn deny_broken_links(self, deny_broken_links: bool) -> Self {
+    trace!("Setting deny_broken_links to {}", deny_broken_links);
+    Self(Launchpad {
+      deny_broken_links,
+      ..self.0
+    })
+  }
+
+  /// Set [`Feeds::atom`].
+  #[instrument(skip(self))]
+  pub fn feeds_atom(self, atom: bool) -> Self {
+    trace!("Setting feeds.atom to {}", atom);
+    Self(Launchpad {
+      feeds: Feeds {
+        atom,
+        ..self.0.feeds
+      },
+      ..self.0
+    })
+  }
+
+  /// Set [`Feeds::limit`].
+  #[instrument(skip(self))]
+  pub fn feeds_limit(self, limit: Option<usize>) -> Self {
+    trace!("Setting feeds.limit to {:?}", limit);
+    Self(Launchpad {
+      feeds: Feeds {
+        limit,
+        ..self.0.feeds
+      },
+      ..self.0
+    })
+  }
+
+  /// Set [`Feeds::rss`].
+  #[instrument(skip(self))]
+  pub fn feeds_rss(self, rss: bool) -> Self {
+    trace!("Setting feeds.rss to {}", rss);
+    Self(Launchpad {
+      feeds: Feeds {
+        rss,
+        ..self.0.feeds
+      },
+      ..self.0
+    })
+  }
+
+  /// Set [`Feeds::sections`].
+  #[instrument(skip(self))]
+  pub fn feeds_sections(self, sections: bool) -> Self {
+    trace!("Setting feeds.sections to {}", sections);
+    Self(Launchpad {
+      feeds: Feeds {
+        sections,
+        ..self.0.feeds
+      },
+      ..self.0
+    })
+  }
+
+  /// Set [`Feeds::taxonomies`].
+  #[instrument(skip(self))]
+  pub fn feeds_taxonomies(self, taxonomies: bool) -> Self {
+    trace!("Setting feeds.taxonomies to {}", taxonomies);
+    Self(Launchpad {
+      feeds: Feeds {
+        taxonomies,
+        ..self.0.feeds
+      },
+      ..self.0
+    })
+  }
+
+  /// Set [`Feeds::title`].
+  #[instrument(skip(self))]
+  pub fn feeds_title(self, title: &str) -> Self {
+    trace!("Setting feeds.title to {}", title);
+    Self(Launchpad {
+      feeds: Feeds {
+        title: Some(title.to_string()),
+        ..self.0.feeds
+      },
+      ..self.0
+    })
+  }
+
+  /// Set [`Launchpad::heading_anchors`].
+  #[instrument(skip(self))]
+  pub fn heading_anchors(self, heading_anchors: bool) -> Self {
+    trace!("Setting heading_anchors to {}", heading_anchors);
+    Self(Launchpad {
+      heading_anchors,
+      ..self.0
+    })
+  }
+
+  /// Set [`Launchpad::incremental`].
+  #[instrument(skip(self))]
+  pub fn incremental(self, incremental: bool) -> Self {
+    trace!("Setting incremental to {}", incremental);
+    Self(Launchpad {
+      incremental,
+      ..self.0
+    })
+  }
+
   /// Set [`Launchpad::mode`].
   #[instrument(skip(self))]
   pub fn mode(self, mode: Mode) -> Self {
@@ -289,6 +1222,75 @@ impl LaunchpadBuilder {
     })
   }
 
+  /// Set [`Launchpad::pretty_urls`].
+  #[instrument(skip(self))]
+  pub fn pretty_urls(self, pretty_urls: bool) -> Self {
+    trace!("Setting pretty_urls to {}", pretty_urls);
+    Self(Launchpad {
+      pretty_urls,
+      ..self.0
+    })
+  }
+
+  /// Set [`Launchpad::robots_txt`].
+  #[instrument(skip(self))]
+  pub fn robots_txt(self, robots_txt: bool) -> Self {
+    trace!("Setting robots_txt to {}", robots_txt);
+    Self(Launchpad {
+      robots_txt,
+      ..self.0
+    })
+  }
+
+  /// Set [`SearchIndex::enabled`].
+  #[instrument(skip(self))]
+  pub fn search_index(self, enabled: bool) -> Self {
+    trace!("Setting search_index.enabled to {}", enabled);
+    Self(Launchpad {
+      search_index: SearchIndex {
+        enabled,
+        ..self.0.search_index
+      },
+      ..self.0
+    })
+  }
+
+  /// Set [`SearchIndex::fields`].
+  #[instrument(skip(self))]
+  pub fn search_index_fields(self, fields: Vec<SearchField>) -> Self {
+    trace!("Setting search_index.fields to {:?}", fields);
+    Self(Launchpad {
+      search_index: SearchIndex {
+        fields,
+        ..self.0.search_index
+      },
+      ..self.0
+    })
+  }
+
+  /// Set [`SearchIndex::max_text_length`].
+  #[instrument(skip(self))]
+  pub fn search_index_max_text_length(self, max_text_length: usize) -> Self {
+    trace!(
+      "Setting search_index.max_text_length to {}",
+      max_text_length
+    );
+    Self(Launchpad {
+      search_index: SearchIndex {
+        max_text_length,
+        ..self.0.search_index
+      },
+      ..self.0
+    })
+  }
+
+  /// Set [`Launchpad::sitemap`].
+  #[instrument(skip(self))]
+  pub fn sitemap(self, sitemap: bool) -> Self {
+    trace!("Setting sitemap to {}", sitemap);
+    Self(Launchpad { sitemap, ..self.0 })
+  }
+
   /// Set [`Launchpad::source`].
   #[instrument(skip(self))]
   pub fn source(self, source: &str) -> Self {
@@ -314,6 +1316,21 @@ impl LaunchpadBuilder {
     })
   }
 
+  /// Set [`Launchpad::taxonomies`] from their names.
+  #[instrument(skip(self))]
+  pub fn taxonomies(self, taxonomies: Vec<&str>) -> Self {
+    let taxonomies = taxonomies
+      .into_iter()
+      .map(Taxonomy::new)
+      .collect::<Vec<_>>();
+
+    trace!("Setting taxonomies to {:?}", taxonomies);
+    Self(Launchpad {
+      taxonomies,
+      ..self.0
+    })
+  }
+
   /// Set [`Templates::default`].
   #[instrument(skip(self))]
   pub fn templates_default(self, default: &str) -> Self {
@@ -340,28 +1357,66 @@ impl LaunchpadBuilder {
     })
   }
 
+  /// Set [`Launchpad::threads`].
+  #[instrument(skip(self))]
+  pub fn threads(self, threads: usize) -> Self {
+    trace!("Setting threads to {}", threads);
+    Self(Launchpad { threads, ..self.0 })
+  }
+
   /// Finalize the build and return the resulting [`Launchpad`].
   #[instrument(skip(self))]
   pub fn build(self) -> Result<Launchpad> {
+    for taxonomy in &self.0.taxonomies {
+      taxonomy.validate()?;
+    }
+
+    let current_dir = current_dir().at(".")?;
     let source = &self.0.source;
-    let directory =
-      current_dir()?.join(source.join(&self.0.templates.directory));
+    let directory = current_dir.join(source.join(&self.0.templates.directory));
     let directory = directory.to_str().unwrap();
     let files = if directory.ends_with('/') {
       directory.to_string()
     } else {
       directory.to_string() + "/"
-    } + "**/*.html";
+    } + "**/*.{html,txt,xml}";
 
     debug!("{}", files);
-    let mut tera = Tera::new(&files)?;
+    let mut tera = Tera::new(&files).map_err(|source| Error::Template {
+      document: None,
+      name: None,
+      source,
+    })?;
+    // Tera's escaping is for HTML, XML templates use `escape_xml` instead.
+    tera.autoescape_on(vec![".html", ".htm"]);
+    // Templates in the directory take precedence over the built-in ones.
+    builtin_templates()
+      .and_then(|builtin| tera.extend(&builtin))
+      .map_err(|source| Error::Template {
+        document: None,
+        name: None,
+        source,
+      })?;
     tera.register_filter("highlight_code", tera_highlight_code);
     tera.register_filter("json_dump", tera_json_dump);
-    tera.register_filter("render_markdown", tera_render_markdown);
+    tera.register_filter(
+      "render_markdown",
+      tera_render_markdown(
+        &self.0.base_url,
+        &self.0.admonitions,
+        self.0.heading_anchors,
+      ),
+    );
+    tera.register_function("absolute_url", tera_absolute_url(&self.0.base_url));
+    tera.register_function(
+      "taxonomy_url",
+      tera_taxonomy_url(&self.0.base_url, &self.0.taxonomies),
+    );
+    tera.register_function("url_for", tera_url_for(&self.0.base_url));
 
     let launchpad = Launchpad {
-      output: current_dir()?.join(self.0.output),
-      source: current_dir()?.join(self.0.source),
+      output: current_dir.join(self.0.output),
+      source: current_dir.join(self.0.source),
       tera,
       ..self.0
     };
@@ -1,16 +1,59 @@
+/// Incremental build caching.
+pub mod cache;
+
 /// All things belonging to [`Document`](document::Document).
 pub mod document;
 
+/// Takeoff's [`Error`] type.
+pub mod error;
+
+/// Atom and RSS feeds of [`Document`](document::Document)s.
+pub mod feed;
+
 /// All things belonging to [`Launchpad`].
 pub mod launchpad;
 
+/// Checking the links in the generated site.
+pub mod links;
+
 /// Markdown handling and rendering functionality.
 pub mod markdown;
 
+/// Splitting listings of [`Document`](document::Document)s into multiple
+/// pages.
+pub mod pagination;
+
+/// Generating a JSON index for client-side search.
+pub mod search;
+
+/// Grouping [`Document`](document::Document)s into sections based on the
+/// directories they're in.
+pub mod section;
+
+/// A local development server with live reload.
+pub mod serve;
+
+/// Calling templates from Markdown with shortcodes.
+pub mod shortcode;
+
+/// Generating a sitemap and `robots.txt`.
+pub mod sitemap;
+
 /// Syntax highlighting functionality.
 pub mod syntax_highlighting;
 
+/// Grouping [`Document`](document::Document)s by terms like tags or
+/// categories.
+pub mod taxonomy;
+
 /// Extra templating functionality for [`tera`].
 pub mod templating;
 
+/// Helpers to build URLs for [`Launchpad::base_url`].
+pub mod url;
+
+/// Functionality to watch for changes and rebuild automatically.
+pub mod watch;
+
+pub use error::{Error, Result};
 pub use launchpad::Launchpad;
@@ -0,0 +1,331 @@
+use std::{
+  collections::{HashMap, HashSet},
+  ffi::OsStr,
+  fmt,
+  fs::read_to_string,
+  path::{Path, PathBuf},
+};
+
+use once_cell::sync::Lazy;
+use percent_encoding::percent_decode_str;
+use regex::Regex;
+use tracing::{debug, instrument, trace};
+use walkdir::WalkDir;
+
+use crate::{
+  document::Document,
+  error::{IoResultExt, Result},
+  markdown::LineIndex,
+  url::{is_external, is_root_relative, normalize},
+  Launchpad,
+};
+
+/// Matches HTML tags, so links in text and code blocks (where the `<` is
+/// escaped) are left alone.
+static TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r"<[a-zA-Z][^>]*>").unwrap());
+
+/// Matches the attributes of a tag that are links or anchors.
+static ATTRIBUTE: Lazy<Regex> = Lazy::new(|| {
+  Regex::new(
+    r#"(?i)\s(href|id|name|src)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#,
+  )
+  .unwrap()
+});
+
+/// Matches HTML character references.
+static ENTITY: Lazy<Regex> = Lazy::new(|| {
+  Regex::new(r"&(#[xX][0-9a-fA-F]+|#[0-9]+|amp|apos|gt|lt|quot);").unwrap()
+});
+
+/// A link in the generated site that doesn't lead anywhere, found by
+/// [`Launchpad::check_links`].
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct BrokenLink {
+  /// What's wrong with the link.
+  pub kind: BrokenLinkKind,
+  /// The line in [`BrokenLink::path`] the link is on.
+  pub line: usize,
+  /// The file the link is in. This is the Markdown file of a [`Document`]
+  /// when the link came from its Markdown, and otherwise the HTML file in
+  /// [`Launchpad::output`] it was found in.
+  pub path: PathBuf,
+  /// The URL of the link, as it appears in the HTML.
+  pub url: String,
+}
+
+impl fmt::Display for BrokenLink {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let problem = match self.kind {
+      BrokenLinkKind::MissingAnchor => "Missing anchor",
+      BrokenLinkKind::MissingFile => "Missing file",
+      BrokenLinkKind::MissingPage => "Missing page",
+    };
+
+    write!(
+      f,
+      "{}:{}: {} for {:?}",
+      self.path.display(),
+      self.line,
+      problem,
+      self.url
+    )
+  }
+}
+
+/// The ways a [`BrokenLink`] can be broken.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum BrokenLinkKind {
+  /// The page exists but doesn't have an element with the `id` in the
+  /// fragment of the URL.
+  MissingAnchor,
+  /// The URL points to a file other than a page that doesn't exist, like a
+  /// static file that was never copied to [`Launchpad::output`].
+  MissingFile,
+  /// The URL points to a page that doesn't exist.
+  MissingPage,
+}
+
+/// A link or anchor found in an HTML file.
+struct Attribute {
+  /// Whether this is an `id` or `name` instead of a link.
+  is_anchor: bool,
+  /// The line the attribute is on.
+  line: usize,
+  /// The value of the attribute, with character references decoded.
+  value: String,
+}
+
+impl Launchpad {
+  /// Checks every link in the HTML files in [`Launchpad::output`], after the
+  /// site has been generated from `documents` by [`Launchpad::take_off`].
+  /// Only links within the site are checked, links to other sites and to
+  /// paths outside of [`Launchpad::base_url`] are skipped.
+  ///
+  /// Links are resolved the same way as the development server does, so
+  /// `/about` finds both `about/index.html` and `about.html`. When a link
+  /// points to an anchor, the page has to contain an element with that `id`.
+  ///
+  /// Broken links that come from the Markdown of a [`Document`] are reported
+  /// with the line in the Markdown file, all others with the line in the
+  /// HTML file. The [`BrokenLink`]s are sorted by path and line.
+  #[instrument(skip(self, documents))]
+  pub fn check_links(&self, documents: &[Document]) -> Result<Vec<BrokenLink>> {
+    let documents = documents
+      .iter()
+      .map(|document| (document.output_path.as_path(), document))
+      .collect::<HashMap<_, _>>();
+
+    let mut anchors = HashMap::new();
+    let mut broken = vec![];
+    for file in html_files(&self.output) {
+      let key = file.strip_prefix(&self.output).unwrap().to_path_buf();
+      debug!("Checking links in {:?}", key);
+      let source = read_to_string(&file).at(&file)?;
+      let markdown_links = match documents.get(key.as_path()) {
+        Some(document) => markdown_links(document),
+        None => HashMap::new(),
+      };
+
+      for attribute in attributes(&source) {
+        if attribute.is_anchor {
+          continue;
+        }
+
+        let kind = match self.check_link(&key, &attribute.value, &mut anchors) {
+          Some(kind) => kind,
+          None => continue,
+        };
+
+        trace!("Broken link to {:?} in {:?}", attribute.value, key);
+        let (path, line) = match markdown_links.get(attribute.value.as_str()) {
+          Some(line) => (documents[key.as_path()].source_path.clone(), *line),
+          None => (file.clone(), attribute.line),
+        };
+
+        broken.push(BrokenLink {
+          kind,
+          line,
+          path,
+          url: attribute.value,
+        });
+      }
+    }
+
+    broken.sort_by(|a, b| (&a.path, a.line).cmp(&(&b.path, b.line)));
+    broken.dedup();
+    Ok(broken)
+  }
+
+  /// Checks a link in the page at `page`, relative to [`Launchpad::output`],
+  /// and returns what's wrong with it if it's broken. The anchors of every
+  /// page that's been looked at are kept in `anchors`.
+  fn check_link(
+    &self,
+    page: &Path,
+    url: &str,
+    anchors: &mut HashMap<PathBuf, HashSet<String>>,
+  ) -> Option<BrokenLinkKind> {
+    if is_external(url) {
+      return None;
+    }
+
+    let (url, fragment) = match url.split_once('#') {
+      Some((url, fragment)) => (url, Some(fragment)),
+      None => (url, None),
+    };
+    let url = url.split('?').next().unwrap();
+
+    let target = if url.is_empty() {
+      page.to_path_buf()
+    } else {
+      let relative = if is_root_relative(url) {
+        // Links outside of the base URL can't be checked.
+        let base_path = self.url("/");
+        if format!("{}/", url) == base_path {
+          ""
+        } else {
+          url.strip_prefix(&base_path)?
+        }
+      } else {
+        url
+      };
+
+      let directory = if is_root_relative(url) {
+        Path::new("")
+      } else {
+        page.parent().unwrap()
+      };
+      let decoded = percent_decode_str(relative).decode_utf8_lossy();
+      let path = normalize(&directory.join(decoded.as_ref()));
+      if path.starts_with("..") {
+        return Some(BrokenLinkKind::MissingPage);
+      }
+
+      let mut candidates = vec![path.join("index.html")];
+      if !relative.is_empty() && !relative.ends_with('/') {
+        candidates.insert(0, path.clone());
+        if path.extension().is_none() {
+          candidates.push(path.with_extension("html"));
+        }
+      }
+
+      match candidates
+        .into_iter()
+        .find(|candidate| self.output.join(candidate).is_file())
+      {
+        Some(target) => target,
+        None => {
+          let extension = path.extension().and_then(OsStr::to_str);
+          return match extension {
+            Some(extension) if extension != "html" => {
+              Some(BrokenLinkKind::MissingFile)
+            }
+            _ => Some(BrokenLinkKind::MissingPage),
+          };
+        }
+      }
+    };
+
+    let fragment = match fragment {
+      Some(fragment) if !fragment.is_empty() => fragment,
+      _ => return None,
+    };
+
+    if target.extension() != Some(OsStr::new("html")) {
+      return None;
+    }
+
+    let ids = anchors.entry(target.clone()).or_insert_with(|| {
+      read_to_string(self.output.join(&target))
+        .map(|source| {
+          attributes(&source)
+            .into_iter()
+            .filter(|attribute| attribute.is_anchor)
+            .map(|attribute| attribute.value)
+            .collect()
+        })
+        .unwrap_or_default()
+    });
+
+    let fragment = percent_decode_str(fragment).decode_utf8_lossy();
+    if ids.contains(fragment.as_ref()) {
+      None
+    } else {
+      Some(BrokenLinkKind::MissingAnchor)
+    }
+  }
+}
+
+/// Returns every HTML file in `directory`, sorted by path.
+fn html_files(directory: &Path) -> Vec<PathBuf> {
+  WalkDir::new(directory)
+    .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+    .into_iter()
+    .filter_map(|entry| entry.ok())
+    .map(|entry| entry.into_path())
+    .filter(|path| path.extension() == Some(OsStr::new("html")))
+    .collect()
+}
+
+/// Returns the links in the Markdown of a [`Document`] as they end up in the
+/// HTML, with the line in the Markdown file they're on.
+fn markdown_links(document: &Document) -> HashMap<&str, usize> {
+  let mut links = HashMap::new();
+  for link in &document.links {
+    links
+      .entry(link.url.as_str())
+      .or_insert(document.front_matter_lines + link.line);
+  }
+
+  links
+}
+
+/// Finds the links and anchors in the tags of some HTML.
+fn attributes(source: &str) -> Vec<Attribute> {
+  let lines = &LineIndex::new(source);
+  TAG
+    .find_iter(source)
+    .flat_map(|tag| {
+      ATTRIBUTE.captures_iter(tag.as_str()).map(move |captures| {
+        let value = captures
+          .get(2)
+          .or_else(|| captures.get(3))
+          .or_else(|| captures.get(4))
+          .unwrap();
+        Attribute {
+          is_anchor: matches!(
+            captures[1].to_ascii_lowercase().as_str(),
+            "id" | "name"
+          ),
+          line: lines.line(tag.start() + value.start()),
+          value: decode_entities(value.as_str()),
+        }
+      })
+    })
+    .collect()
+}
+
+/// Decodes the HTML character references that Tera and [`pulldown_cmark`]
+/// use when escaping.
+fn decode_entities(source: &str) -> String {
+  ENTITY
+    .replace_all(source, |captures: &regex::Captures| {
+      let entity = &captures[1];
+      let character = match entity {
+        "amp" => Some('&'),
+        "apos" => Some('\''),
+        "gt" => Some('>'),
+        "lt" => Some('<'),
+        "quot" => Some('"'),
+        _ => match entity[1..].strip_prefix(['x', 'X']) {
+          Some(hex) => u32::from_str_radix(hex, 16).ok(),
+          None => entity[1..].parse().ok(),
+        }
+        .and_then(char::from_u32),
+      };
+      character.map_or_else(|| captures[0].to_string(), String::from)
+    })
+    .into_owned()
+}
@@ -1,27 +1,538 @@
+use std::{
+  collections::{BTreeMap, HashMap, HashSet},
+  ops::Range,
+  path::{Path, PathBuf},
+};
+
+use percent_encoding::percent_decode_str;
 use pulldown_cmark::{
-  html::push_html, CodeBlockKind, Event, Options, Parser, Tag,
+  escape::escape_html, html::push_html, CodeBlockKind, Event, Options, Parser,
+  Tag,
 };
-use syntect::parsing::SyntaxSet;
+use serde::{Deserialize, Serialize};
+use slug::slugify;
 
-use crate::syntax_highlighting::highlight_code;
+use crate::{
+  document::Document,
+  syntax_highlighting::{highlight_code, SYNTAX_SET},
+  url::{is_external, is_root_relative, normalize, relative_url},
+};
 
 /// Renders some Markdown to HTML using [`pulldown_cmark`].
 pub fn render_markdown(source: &str) -> String {
+  render_markdown_with_base_url(source, "/")
+}
+
+/// The same as [`render_markdown`] but the URLs of links and images that
+/// start with a `/` get the path of `base_url` put in front of them, see
+/// [`relative_url`] for details. URLs in raw HTML are left as they are.
+///
+/// Links to `.md` files are only resolved by [`render_markdown_parts`] with
+/// [`MarkdownOptions::permalinks`], here they're left as they are too.
+pub fn render_markdown_with_base_url(source: &str, base_url: &str) -> String {
+  let options = MarkdownOptions {
+    base_url,
+    ..MarkdownOptions::default()
+  };
+  render_markdown_parts(source, &options).html
+}
+
+/// Options for [`render_markdown_parts`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct MarkdownOptions<'a> {
+  /// The [`Admonitions`] to render blockquotes that start with a marker like
+  /// `[!NOTE]` as.
+  ///
+  /// Defaults to `None`, which renders them as regular blockquotes.
+  pub admonitions: Option<&'a Admonitions>,
+  /// The URLs of links and images that start with a `/` get the path of this
+  /// put in front of them, see [`render_markdown_with_base_url`].
+  ///
+  /// Defaults to `"/"`.
+  pub base_url: &'a str,
+  /// If set to `true`, every heading gets a link to itself added to the end
+  /// of it, like `<a class="heading-anchor" href="#id">#</a>`.
+  ///
+  /// Defaults to `false`.
+  pub heading_anchors: bool,
+  /// The path of the Markdown file being rendered, which relative links to
+  /// `.md` files are resolved from.
+  ///
+  /// Defaults to `None`.
+  pub path: Option<&'a Path>,
+  /// The [`SourcePermalinks`] to resolve links to `.md` files with. Together
+  /// with [`MarkdownOptions::path`], links and images whose URL points to the
+  /// source file of a [`Document`] are changed to point to its permalink
+  /// instead, keeping the fragment. Links to `.md` files that aren't a
+  /// document end up in [`RenderedMarkdown::missing_links`].
+  ///
+  /// Defaults to `None`, which leaves links to `.md` files as they are.
+  pub permalinks: Option<&'a SourcePermalinks>,
+}
+
+impl Default for MarkdownOptions<'_> {
+  fn default() -> Self {
+    Self {
+      admonitions: None,
+      base_url: "/",
+      heading_anchors: false,
+      path: None,
+      permalinks: None,
+    }
+  }
+}
+
+impl MarkdownOptions<'_> {
+  /// Returns the URL a link or image in the Markdown should have in the HTML,
+  /// or `None` when it points to a `.md` file that isn't a [`Document`].
+  pub fn rewrite_url(&self, url: &str) -> Option<String> {
+    if let (Some(path), Some(permalinks)) = (self.path, self.permalinks) {
+      let (target, fragment) = match url.find(['?', '#']) {
+        Some(index) => url.split_at(index),
+        None => (url, ""),
+      };
+
+      if !is_external(target) && target.ends_with(".md") {
+        let permalink = permalinks.resolve(path, target)?;
+        return Some(relative_url(self.base_url, permalink) + fragment);
+      }
+    }
+
+    if is_root_relative(url) {
+      Some(relative_url(self.base_url, url))
+    } else {
+      Some(url.to_string())
+    }
+  }
+}
+
+/// Admonition settings for [`Launchpad`](crate::Launchpad).
+///
+/// Admonitions, also known as callouts, are blockquotes that start with a
+/// marker for their kind, the same as on GitHub:
+///
+/// ```md
+/// > [!WARNING]
+/// > This can't be **undone**.
+/// ```
+///
+/// When enabled, these are rendered as an `<aside>` with the kind as an extra
+/// class and a title, instead of as a blockquote:
+///
+/// ```html
+/// <aside class="admonition warning">
+/// <p class="admonition-title">Warning</p>
+/// <p>This can't be <strong>undone</strong>.</p>
+/// </aside>
+/// ```
+///
+/// A different title can be put after the marker, like `> [!TIP] Pro tip`.
+/// The kind is case-insensitive and blockquotes with a kind that isn't in
+/// [`Admonitions::titles`] are left as they are.
+#[derive(Clone, Debug, Deserialize, Hash, Serialize)]
+#[non_exhaustive]
+#[serde(default)]
+pub struct Admonitions {
+  /// The class of every admonition. The title gets this class followed by
+  /// `-title`.
+  ///
+  /// Defaults to `"admonition"`.
+  pub class: String,
+  /// If set to `true`, admonitions are rendered.
+  ///
+  /// Defaults to `false`.
+  pub enabled: bool,
+  /// The kinds of admonitions in lowercase, with their default titles.
+  /// Setting this replaces the defaults, so it should list every kind that's
+  /// used.
+  ///
+  /// Defaults to the kinds GitHub supports: `note`, `tip`, `important`,
+  /// `warning` and `caution`.
+  pub titles: BTreeMap<String, String>,
+}
+
+impl Default for Admonitions {
+  fn default() -> Self {
+    let titles = [
+      ("caution", "Caution"),
+      ("important", "Important"),
+      ("note", "Note"),
+      ("tip", "Tip"),
+      ("warning", "Warning"),
+    ];
+
+    Self {
+      class: "admonition".to_string(),
+      enabled: false,
+      titles: titles
+        .iter()
+        .map(|(kind, title)| (kind.to_string(), title.to_string()))
+        .collect(),
+    }
+  }
+}
+
+impl Admonitions {
+  /// Replaces the blockquotes that are admonitions in some parsed Markdown
+  /// with the HTML to open and close them, and removes their markers.
+  fn apply(&self, events: &mut Vec<(Event, Range<usize>)>) {
+    let mut index = 0;
+    while index < events.len() {
+      if let Event::Start(Tag::BlockQuote) = events[index].0 {
+        self.apply_at(events, index);
+      }
+
+      index += 1;
+    }
+  }
+
+  /// Turns the blockquote starting at `start` into an admonition, if it
+  /// starts with a marker of one of the [`Admonitions::titles`].
+  fn apply_at(&self, events: &mut Vec<(Event, Range<usize>)>, start: usize) {
+    if !matches!(
+      events.get(start + 1),
+      Some((Event::Start(Tag::Paragraph), _))
+    ) {
+      return;
+    }
+
+    // The marker can be split into multiple text events, so join them up to
+    // the end of the line.
+    let mut marker = String::new();
+    let mut marker_end = start + 2;
+    while let Some((Event::Text(text), _)) = events.get(marker_end) {
+      marker.push_str(text);
+      marker_end += 1;
+    }
+
+    let ends_paragraph = match events.get(marker_end) {
+      Some((Event::End(Tag::Paragraph), _)) => true,
+      Some((Event::SoftBreak | Event::HardBreak, _)) => false,
+      _ => return,
+    };
+
+    let (kind, title) = match parse_marker(&marker) {
+      Some(marker) => marker,
+      None => return,
+    };
+    let title = match (title, self.titles.get(&kind)) {
+      (_, None) => return,
+      (Some(title), _) => title,
+      (None, Some(title)) => title.as_str(),
+    };
+
+    let mut depth = 0;
+    let end = events[start..]
+      .iter()
+      .position(|(event, _)| {
+        match event {
+          Event::Start(Tag::BlockQuote) => depth += 1,
+          Event::End(Tag::BlockQuote) => depth -= 1,
+          _ => (),
+        }
+        depth == 0
+      })
+      .map(|offset| start + offset)
+      .expect("Blockquotes are always closed");
+
+    let mut open = String::new();
+    open.push_str("<aside class=\"");
+    escape_html(&mut open, &self.class).unwrap();
+    open.push(' ');
+    escape_html(&mut open, &kind).unwrap();
+    open.push_str("\">\n<p class=\"");
+    escape_html(&mut open, &self.class).unwrap();
+    open.push_str("-title\">");
+    escape_html(&mut open, title).unwrap();
+    open.push_str("</p>\n");
+
+    events[end].0 = Event::Html("</aside>\n".into());
+    events[start].0 = Event::Html(open.into());
+    // When the marker has its own line, the rest of its paragraph is kept.
+    let marker_start = if ends_paragraph { start + 1 } else { start + 2 };
+    events.drain(marker_start..=marker_end);
+  }
+}
+
+/// Parses the marker of an admonition like `[!NOTE] Title` into the kind in
+/// lowercase and the title, if there is one.
+fn parse_marker(marker: &str) -> Option<(String, Option<&str>)> {
+  let marker = marker.trim_start().strip_prefix("[!")?;
+  let end = marker.find(']')?;
+  let kind = &marker[..end];
+  if kind.is_empty() || !kind.chars().all(|c| c.is_ascii_alphabetic()) {
+    return None;
+  }
+
+  let title = marker[end + 1..].trim();
+  Some((
+    kind.to_ascii_lowercase(),
+    (!title.is_empty()).then_some(title),
+  ))
+}
+
+/// The permalinks of [`Document`]s keyed by their source files, to resolve
+/// links to `.md` files with. See [`MarkdownOptions::permalinks`].
+#[derive(Debug, Default)]
+pub struct SourcePermalinks {
+  /// The permalinks keyed by the normalized path of the source file.
+  permalinks: HashMap<PathBuf, String>,
+  /// The directory that links starting with a `/` are relative to.
+  source: PathBuf,
+}
+
+impl SourcePermalinks {
+  /// Collects the permalinks of [`Document`]s, whose source files are in
+  /// `source`. Links starting with a `/` are resolved relative to `source`.
+  pub fn new(source: &Path, documents: &[Document]) -> Self {
+    Self {
+      permalinks: documents
+        .iter()
+        .map(|document| {
+          (normalize(&document.source_path), document.permalink.clone())
+        })
+        .collect(),
+      source: normalize(source),
+    }
+  }
+
+  /// Returns the permalink of the [`Document`] that a link to `target` in the
+  /// Markdown file at `path` points to, if there is one.
+  pub fn resolve(&self, path: &Path, target: &str) -> Option<&str> {
+    let target = percent_decode_str(target).decode_utf8_lossy();
+    let file = match target.strip_prefix('/') {
+      Some(target) => self.source.join(target),
+      None => path.parent()?.join(target.as_ref()),
+    };
+    self.permalinks.get(&normalize(&file)).map(String::as_str)
+  }
+}
+
+/// A link in Markdown to a `.md` file that isn't a [`Document`].
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
+pub struct MissingLink {
+  /// The line in the Markdown the link is on, starting at 1.
+  pub line: usize,
+  /// The URL of the link.
+  pub url: String,
+}
+
+/// A link or image in Markdown.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct MarkdownLink {
+  /// The line in the Markdown the link is on, starting at 1.
+  pub line: usize,
+  /// The URL of the link as it ends up in the HTML, see
+  /// [`MarkdownOptions::rewrite_url`].
+  pub url: String,
+}
+
+/// The byte offsets that the lines in some text start at, to find the line an
+/// offset is on without counting every newline before it.
+pub(crate) struct LineIndex(Vec<usize>);
+
+impl LineIndex {
+  /// Finds the start of every line in `source`.
+  pub fn new(source: &str) -> Self {
+    let starts = source.match_indices('\n').map(|(index, _)| index + 1);
+    Self(std::iter::once(0).chain(starts).collect())
+  }
+
+  /// Returns the line number of the byte `offset`, starting at 1.
+  pub fn line(&self, offset: usize) -> usize {
+    self.0.partition_point(|&start| start <= offset)
+  }
+}
+
+/// Markdown rendered by [`render_markdown_parts`].
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct RenderedMarkdown {
+  /// The rendered HTML.
+  pub html: String,
+  /// Every link and image, except the ones in
+  /// [`RenderedMarkdown::missing_links`].
+  pub links: Vec<MarkdownLink>,
+  /// The links to `.md` files that aren't a [`Document`], see
+  /// [`MarkdownOptions::permalinks`].
+  pub missing_links: Vec<MissingLink>,
+  /// The plain text without any markup or raw HTML, with all whitespace
+  /// collapsed into single spaces.
+  pub text: String,
+  /// The table of contents, made from the headings.
+  pub toc: Vec<Heading>,
+}
+
+/// A heading in the table of contents of some Markdown.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct Heading {
+  /// The headings after this one with a higher level, up until the next
+  /// heading with the same or a lower level.
+  pub children: Vec<Heading>,
+  /// The `id` attribute of the heading, which is the slug of its text. When
+  /// multiple headings have the same slug, the ones after the first get a
+  /// number at the end, like `usage-1`.
+  pub id: String,
+  /// The level of the heading, from 1 for `<h1>` to 6 for `<h6>`.
+  pub level: u32,
+  /// The plain text of the heading.
+  pub title: String,
+}
+
+impl Heading {
+  /// Adds a heading to a table of contents, as a child of the last heading
+  /// when its level is higher.
+  fn insert_into(self, toc: &mut Vec<Heading>) {
+    match toc.last_mut() {
+      Some(last) if last.level < self.level => {
+        self.insert_into(&mut last.children)
+      }
+      _ => toc.push(self),
+    }
+  }
+
+  /// Returns this heading and all of its children, depth-first.
+  pub fn flatten(&self) -> Vec<&Heading> {
+    let mut headings = vec![self];
+    for child in &self.children {
+      headings.extend(child.flatten());
+    }
+    headings
+  }
+}
+
+/// Returns a unique `id` for a heading with `title`, adding it to the ids
+/// that have been used already.
+fn heading_id(title: &str, used: &mut HashSet<String>) -> String {
+  let mut slug = slugify(title);
+  if slug.is_empty() {
+    slug = "heading".to_string();
+  }
+
+  let mut id = slug.clone();
+  let mut number = 0;
+  while used.contains(&id) {
+    number += 1;
+    id = format!("{}-{}", slug, number);
+  }
+
+  used.insert(id.clone());
+  id
+}
+
+/// The same as [`render_markdown_with_base_url`] but with more
+/// [`MarkdownOptions`], and the table of contents and plain text are collected
+/// from the Markdown while rendering it as well.
+///
+/// Every heading gets an `id` so it can be linked to, see [`Heading::id`].
+pub fn render_markdown_parts(
+  source: &str,
+  options: &MarkdownOptions,
+) -> RenderedMarkdown {
   // Create the parser with all options enabled.
   let parser = Parser::new_ext(source, Options::all());
 
-  // Load the syntaxes from Syntect.
-  let syntax_set = SyntaxSet::load_defaults_newlines();
+  // Use the syntaxes from Syntect that are shared between renders.
+  let syntax_set = &*SYNTAX_SET;
 
   // Define some state we'll use in the rendering.
   let mut code_language = String::new();
   let mut code_to_highlight = String::new();
   let mut events = vec![];
+  let mut heading: Option<(usize, String)> = None;
   let mut in_code_block = false;
+  let mut links = vec![];
+  let lines = LineIndex::new(source);
+  let mut missing_links = vec![];
   let mut syntax = syntax_set.find_syntax_plain_text();
+  let mut text = String::new();
+  let mut toc = vec![];
+  let mut used_ids = HashSet::new();
+
+  // Links and images are rewritten the same way, and collected into either
+  // `links` or `missing_links`.
+  let mut rewrite_url = |url: &str, start: usize| {
+    let line = lines.line(start);
+    match options.rewrite_url(url) {
+      Some(rewritten) => {
+        links.push(MarkdownLink {
+          line,
+          url: rewritten.clone(),
+        });
+        rewritten
+      }
+      None => {
+        missing_links.push(MissingLink {
+          line,
+          url: url.to_string(),
+        });
+        url.to_string()
+      }
+    }
+  };
+
+  let mut parsed = parser.into_offset_iter().collect::<Vec<_>>();
+  if let Some(admonitions) = options.admonitions {
+    admonitions.apply(&mut parsed);
+  }
+
+  for (event, range) in parsed {
+    match &event {
+      Event::Text(content) | Event::Code(content) => {
+        text.push_str(content);
+        if let Some((_, title)) = &mut heading {
+          title.push_str(content);
+        }
+      }
+      // Keep words from separate lines and blocks apart.
+      Event::SoftBreak
+      | Event::HardBreak
+      | Event::End(
+        Tag::BlockQuote
+        | Tag::CodeBlock(_)
+        | Tag::FootnoteDefinition(_)
+        | Tag::Item
+        | Tag::Paragraph
+        | Tag::TableCell,
+      ) => text.push(' '),
+      _ => (),
+    }
 
-  for event in parser {
     match event {
+      Event::Start(Tag::Heading(_)) => {
+        // The opening tag needs the id, which is only known once the whole
+        // heading has been seen, so leave a placeholder to replace later.
+        heading = Some((events.len(), String::new()));
+        events.push(Event::Html("".into()));
+      }
+      Event::End(Tag::Heading(level)) => {
+        let (start, title) = heading.take().unwrap_or_default();
+        let title = title.trim().to_string();
+        let id = heading_id(&title, &mut used_ids);
+        text.push(' ');
+
+        events[start] =
+          Event::Html(format!(r#"<h{} id="{}">"#, level, id).into());
+        if options.heading_anchors {
+          events.push(Event::Html(
+            format!(r##"<a class="heading-anchor" href="#{}">#</a>"##, id)
+              .into(),
+          ));
+        }
+        events.push(Event::Html(format!("</h{}>\n", level).into()));
+
+        Heading {
+          children: vec![],
+          id,
+          level,
+          title,
+        }
+        .insert_into(&mut toc);
+      }
       Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(language))) => {
         // When a fenced codeblock is started, assign it to the state.
         code_language = language.to_string();
@@ -38,7 +549,7 @@ pub fn render_markdown(source: &str) -> String {
             format!(
               r#"<pre class="language-{}"><code>{}</code></pre>"#,
               code_language,
-              highlight_code(&code_to_highlight, syntax, &syntax_set)
+              highlight_code(&code_to_highlight, syntax, syntax_set)
             )
             .into(),
           ));
@@ -49,6 +560,14 @@ pub fn render_markdown(source: &str) -> String {
           syntax = syntax_set.find_syntax_plain_text();
         }
       }
+      Event::Start(Tag::Link(kind, url, title)) => {
+        let url = rewrite_url(&url, range.start);
+        events.push(Event::Start(Tag::Link(kind, url.into(), title)));
+      }
+      Event::Start(Tag::Image(kind, url, title)) => {
+        let url = rewrite_url(&url, range.start);
+        events.push(Event::Start(Tag::Image(kind, url.into(), title)));
+      }
       Event::Text(text) => {
         if in_code_block {
           // When we're parsing some text and we're in a code block, add it to
@@ -67,5 +586,11 @@ pub fn render_markdown(source: &str) -> String {
   let mut html = String::new();
   push_html(&mut html, events.into_iter());
 
-  html
+  RenderedMarkdown {
+    html,
+    links,
+    missing_links,
+    text: text.split_whitespace().collect::<Vec<_>>().join(" "),
+    toc,
+  }
 }
@@ -0,0 +1,173 @@
+use std::{
+  collections::{BTreeMap, HashMap},
+  path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+use serde_json::json;
+use tera::Value;
+
+use crate::{
+  document::Document, error::Result, launchpad::GeneratedPage,
+  section::Section, Launchpad,
+};
+
+/// A [`Paginator`] is one page of a listing that's split up into multiple
+/// pages, like a [`Section`](crate::section::Section) with
+/// [`Metadata::paginate_by`](crate::document::Metadata::paginate_by) or a
+/// [`Taxonomy`](crate::taxonomy::Taxonomy) with
+/// [`Taxonomy::paginate_by`](crate::taxonomy::Taxonomy::paginate_by).
+///
+/// The first page is written to the listing's usual location and the others
+/// to `page/<number>/index.html` next to it. Templates receive the current
+/// page as `paginator`.
+#[derive(Debug, Serialize)]
+#[non_exhaustive]
+pub struct Paginator<'a> {
+  /// The number of the current page, starting at 1.
+  pub current_index: usize,
+  /// The URL of the first page.
+  pub first: String,
+  /// The URL of the last page.
+  pub last: String,
+  /// The URL of the next page, if there is one.
+  pub next: Option<String>,
+  /// The [`Document`]s on the current page.
+  pub pages: Vec<&'a Document>,
+  /// The maximum number of [`Document`]s on a page.
+  pub paginate_by: usize,
+  /// The URL of the previous page, if there is one.
+  pub previous: Option<String>,
+  /// The total number of [`Document`]s on all pages.
+  pub total_items: usize,
+  /// The total number of pages.
+  pub total_pages: usize,
+  /// The URL of the current page.
+  pub url: String,
+}
+
+/// Splits `items` into [`Paginator`]s of `paginate_by` documents, for a
+/// listing with the root-relative `permalink`. There's always at least one
+/// page, even when there are no items.
+pub fn paginate<'a>(
+  launchpad: &Launchpad,
+  items: &[&'a Document],
+  paginate_by: usize,
+  permalink: &str,
+) -> Vec<Paginator<'a>> {
+  let paginate_by = paginate_by.max(1);
+  let total_pages = items.len().div_ceil(paginate_by).max(1);
+  let url = |index| launchpad.url(&page_permalink(permalink, index));
+
+  (1..=total_pages)
+    .map(|index| Paginator {
+      current_index: index,
+      first: url(1),
+      last: url(total_pages),
+      next: Some(index + 1).filter(|next| *next <= total_pages).map(url),
+      pages: items
+        .iter()
+        .skip((index - 1) * paginate_by)
+        .take(paginate_by)
+        .copied()
+        .collect(),
+      paginate_by,
+      previous: index
+        .checked_sub(1)
+        .filter(|previous| *previous > 0)
+        .map(url),
+      total_items: items.len(),
+      total_pages,
+      url: url(index),
+    })
+    .collect()
+}
+
+/// Returns the URL of page `index` relative to the root of the site, for a
+/// listing with the root-relative `permalink`. Pages after the first are in
+/// the directory of the `permalink`.
+pub fn page_permalink(permalink: &str, index: usize) -> String {
+  if index == 1 {
+    return permalink.to_string();
+  }
+
+  let directory = match permalink.rfind('/') {
+    Some(end) => &permalink[..=end],
+    None => "/",
+  };
+  format!("{}page/{}/", directory, index)
+}
+
+/// Returns the path to write page `index` to relative to
+/// [`Launchpad::output`], for a listing whose first page is written to
+/// `output_path`.
+pub fn page_output_path(output_path: &Path, index: usize) -> PathBuf {
+  if index == 1 {
+    output_path.to_path_buf()
+  } else {
+    output_path
+      .parent()
+      .unwrap()
+      .join("page")
+      .join(index.to_string())
+      .join("index.html")
+  }
+}
+
+impl Launchpad {
+  /// Paginates every [`Section`] that has an `index.md` with
+  /// [`Metadata::paginate_by`](crate::document::Metadata::paginate_by) set.
+  ///
+  /// The first page is rendered as part of the `index.md` [`Document`], so
+  /// its [`Paginator`] is returned keyed by [`Section::path`]. The other pages
+  /// are returned as [`GeneratedPage`]s that render the same document.
+  pub(crate) fn paginate_sections(
+    &self,
+    sections: &BTreeMap<PathBuf, Section>,
+    section_contexts: &HashMap<PathBuf, Value>,
+    documents: &[Document],
+  ) -> Result<(HashMap<PathBuf, Value>, Vec<GeneratedPage>)> {
+    let mut first_pages = HashMap::new();
+    let mut pages = vec![];
+
+    for section in sections.values() {
+      let (index, paginate_by) = match (section.index, section.paginate_by) {
+        (Some(index), Some(paginate_by)) => (index, paginate_by),
+        _ => continue,
+      };
+
+      let document = &documents[index];
+      let items = section
+        .pages
+        .iter()
+        .map(|page| &documents[*page])
+        .collect::<Vec<_>>();
+      let mut paginators =
+        paginate(self, &items, paginate_by, &document.permalink).into_iter();
+
+      let first_page = serde_json::to_value(paginators.next().unwrap())?;
+      first_pages.insert(section.path.clone(), first_page);
+
+      for paginator in paginators {
+        pages.push(GeneratedPage {
+          context: json!({
+            "document": document,
+            "extra": document.metadata.extra,
+            "metadata": document.metadata,
+            "next": null,
+            "paginator": paginator,
+            "previous": null,
+            "section": section_contexts[&section.path],
+          }),
+          output_path: page_output_path(
+            &document.output_path,
+            paginator.current_index,
+          ),
+          template: self.template(document).to_string(),
+        });
+      }
+    }
+
+    Ok((first_pages, pages))
+  }
+}
@@ -0,0 +1,181 @@
+use std::{
+  collections::HashMap,
+  fs::{create_dir_all, write},
+  path::PathBuf,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use tracing::{debug, trace};
+
+use crate::{
+  cache::{self, BuildCache},
+  document::Document,
+  error::{IoResultExt, Result},
+  markdown::Heading,
+  Launchpad,
+};
+
+/// The file name of the search index.
+pub const SEARCH_INDEX_FILE_NAME: &str = "search_index.json";
+
+/// Search index settings for [`Launchpad`].
+///
+/// When enabled, a JSON array with an object for every rendered [`Document`]
+/// is written to `search_index.json` in the root of [`Launchpad::output`], for
+/// client-side search libraries to load. The objects only have the keys in
+/// [`SearchIndex::fields`], see [`SearchField`] for what they contain.
+///
+/// Like the sitemap, the index leaves out every [`Document`] that is a draft,
+/// also in [`Mode::Development`](crate::launchpad::Mode::Development) where
+/// drafts are rendered.
+#[derive(Debug, Deserialize, Serialize)]
+#[non_exhaustive]
+#[serde(default)]
+pub struct SearchIndex {
+  /// If set to `true`, the search index is generated.
+  ///
+  /// Defaults to `false`.
+  pub enabled: bool,
+  /// The fields to include for every [`Document`].
+  ///
+  /// Defaults to all of them.
+  pub fields: Vec<SearchField>,
+  /// The maximum number of characters of [`SearchField::Text`] to include for
+  /// every [`Document`], to keep the index small. The text is cut off at the
+  /// last word that fits.
+  ///
+  /// Defaults to `10000`.
+  pub max_text_length: usize,
+}
+
+impl Default for SearchIndex {
+  fn default() -> Self {
+    Self {
+      enabled: false,
+      fields: vec![
+        SearchField::Headings,
+        SearchField::Tags,
+        SearchField::Text,
+        SearchField::Title,
+        SearchField::Url,
+      ],
+      max_text_length: 10000,
+    }
+  }
+}
+
+impl SearchIndex {
+  /// Creates the entry in the search index for a [`Document`].
+  pub fn entry(&self, document: &Document) -> Map<String, Value> {
+    let metadata = &document.metadata;
+    self
+      .fields
+      .iter()
+      .map(|field| {
+        let value = match field {
+          SearchField::Headings => json!(document
+            .toc
+            .iter()
+            .flat_map(Heading::flatten)
+            .map(|heading| &heading.title)
+            .collect::<Vec<_>>()),
+          SearchField::Tags => json!(metadata.taxonomies.get("tags")),
+          SearchField::Text => {
+            json!(truncate_words(&document.text, self.max_text_length))
+          }
+          SearchField::Title => json!(metadata.title),
+          SearchField::Url => json!(document.url),
+        };
+        (field.name().to_string(), value)
+      })
+      .collect()
+  }
+}
+
+/// The fields that can be included in the [`SearchIndex`].
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[non_exhaustive]
+#[serde(rename_all = "lowercase")]
+pub enum SearchField {
+  /// The text of the headings in the Markdown, see [`Document::toc`].
+  Headings,
+  /// The `tags` in
+  /// [`Metadata::taxonomies`](crate::document::Metadata::taxonomies), or
+  /// `null` when there are none.
+  Tags,
+  /// The plain text of the Markdown, see [`Document::text`] and
+  /// [`SearchIndex::max_text_length`].
+  Text,
+  /// The [`Metadata::title`](crate::document::Metadata::title).
+  Title,
+  /// The [`Document::url`].
+  Url,
+}
+
+impl SearchField {
+  /// Returns the key this field has in the [`SearchIndex`].
+  pub fn name(self) -> &'static str {
+    match self {
+      Self::Headings => "headings",
+      Self::Tags => "tags",
+      Self::Text => "text",
+      Self::Title => "title",
+      Self::Url => "url",
+    }
+  }
+}
+
+/// Cuts `text` off after at most `max_length` characters, at the end of the
+/// last word that fits if there is one.
+fn truncate_words(text: &str, max_length: usize) -> &str {
+  let end = match text.char_indices().nth(max_length) {
+    Some((end, _)) => end,
+    None => return text,
+  };
+
+  if text[end..].starts_with(' ') {
+    return &text[..end];
+  }
+
+  match text[..end].rfind(' ') {
+    Some(space) => &text[..space],
+    None => &text[..end],
+  }
+}
+
+impl Launchpad {
+  /// Writes the [`SearchIndex`] when it's enabled and has changed since the
+  /// previous build.
+  pub(crate) fn write_search_index(
+    &self,
+    documents: &[Document],
+    cache: &BuildCache,
+    outputs: &mut HashMap<PathBuf, u64>,
+  ) -> Result<()> {
+    if !self.search_index.enabled {
+      return Ok(());
+    }
+
+    let entries = documents
+      .iter()
+      .filter(|document| !document.metadata.is_draft)
+      .map(|document| self.search_index.entry(document))
+      .collect::<Vec<_>>();
+    let json = serde_json::to_string(&entries)?;
+
+    let key = PathBuf::from(SEARCH_INDEX_FILE_NAME);
+    let destination = self.output.join(&key);
+    let fingerprint = cache::hash(&json);
+    let fresh = cache.is_fresh(&key, fingerprint, &destination);
+    outputs.insert(key, fingerprint);
+    if fresh {
+      trace!("Skipping unchanged {:?}", destination);
+      return Ok(());
+    }
+
+    create_dir_all(&self.output).at(&self.output)?;
+    debug!("Writing {:?}", destination);
+    write(&destination, json).at(destination)
+  }
+}
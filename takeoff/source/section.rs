@@ -0,0 +1,358 @@
+use std::{
+  cmp::{Ordering, Reverse},
+  collections::{BTreeMap, HashMap},
+  path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use tera::Value;
+
+use crate::{
+  document::{Document, Metadata},
+  error::Result,
+  Launchpad,
+};
+
+/// A [`Section`] is a directory in [`Launchpad::source`] that contains
+/// [`Document`]s, either directly or in one of its subdirectories. The
+/// `index.md` in the directory is the section's own page.
+///
+/// When rendering, templates receive the section a document is in as
+/// `section`, which has the following fields:
+///
+/// * `ancestors`: the sections this one is inside of, starting at the root.
+/// * `index`: the `index.md` [`Document`], if there is one.
+/// * `pages`: the other [`Document`]s directly inside this section.
+/// * `parent`: the section this one is directly inside of, if any.
+/// * `path`: the [`Section::path`].
+/// * `permalink` and `url`: the same as for [`Document`]s, for the directory.
+/// * `sort_by`: the [`Section::sort_by`].
+/// * `subsections`: the sections directly inside this one, with the same
+///   fields as this one except `ancestors` and `parent`.
+/// * `title`: the title of the `index.md` [`Document`], if it has one.
+///
+/// The sections in `ancestors` and `parent` only have the `path`,
+/// `permalink`, `title` and `url` fields. The documents in `index` and
+/// `pages` only have the `metadata`, `permalink`, `section` and `url` fields,
+/// so the whole tree stays small enough to give to every page. Their content
+/// is in `documents` and the
+/// [`Paginator`](crate::pagination::Paginator) of paginated sections.
+///
+/// Templates also receive `previous` and `next`, the [`Document`]s before and
+/// after the one being rendered in its section's `pages`. See
+/// [`Section::previous_and_next`] for details.
+#[derive(Debug, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct Section {
+  /// The paths of the sections this one is inside of, starting with the root
+  /// section. The last one is the parent of this section.
+  pub ancestors: Vec<PathBuf>,
+  /// The position of the `index.md` [`Document`] in the documents the
+  /// sections were made from, if this section has one.
+  pub index: Option<usize>,
+  /// Where every position in [`Section::pages`] is in it, so
+  /// [`Section::previous_and_next`] doesn't have to search for it.
+  #[serde(skip)]
+  page_indices: HashMap<usize, usize>,
+  /// The positions of the other [`Document`]s directly inside this section,
+  /// in the documents the sections were made from. They are sorted according
+  /// to [`Section::sort_by`].
+  pub pages: Vec<usize>,
+  /// How many pages to list per page, which is the
+  /// [`Metadata::paginate_by`](crate::document::Metadata::paginate_by) of
+  /// the `index.md` [`Document`].
+  pub paginate_by: Option<usize>,
+  /// The directory of this section relative to [`Launchpad::source`], which
+  /// is empty for the root section.
+  pub path: PathBuf,
+  /// How the [`Section::pages`] are sorted, which is the
+  /// [`Metadata::sort_by`](crate::document::Metadata::sort_by) of the
+  /// `index.md` [`Document`].
+  pub sort_by: SortBy,
+  /// The paths of the sections directly inside this one.
+  pub subsections: Vec<PathBuf>,
+}
+
+impl Section {
+  /// Creates a new, empty [`Section`].
+  fn new(path: PathBuf, ancestors: Vec<PathBuf>) -> Self {
+    Self {
+      ancestors,
+      index: None,
+      page_indices: HashMap::new(),
+      pages: vec![],
+      paginate_by: None,
+      path,
+      sort_by: SortBy::default(),
+      subsections: vec![],
+    }
+  }
+
+  /// Groups [`Document`]s into [`Section`]s using [`Document::section`],
+  /// keyed by their [`Section::path`]. There is always a root section, and
+  /// every section's ancestors are included even if they don't contain any
+  /// documents themselves.
+  pub fn from_documents(documents: &[Document]) -> BTreeMap<PathBuf, Self> {
+    let mut sections = BTreeMap::new();
+    sections.insert(PathBuf::new(), Self::new(PathBuf::new(), vec![]));
+
+    for (position, document) in documents.iter().enumerate() {
+      let mut ancestors = vec![PathBuf::new()];
+      let mut path = PathBuf::new();
+      for component in document.section.iter() {
+        path.push(component);
+        sections
+          .entry(path.clone())
+          .or_insert_with(|| Self::new(path.clone(), ancestors.clone()));
+        ancestors.push(path.clone());
+      }
+
+      let section = sections.get_mut(&document.section).unwrap();
+      if document.is_section_index() {
+        section.index = Some(position);
+        section.paginate_by = document.metadata.paginate_by;
+        section.sort_by = document.metadata.sort_by;
+      } else {
+        section.pages.push(position);
+      }
+    }
+
+    for section in sections.values_mut() {
+      let sort_by = section.sort_by;
+      section
+        .pages
+        .sort_by(|a, b| sort_by.compare(&documents[*a], &documents[*b]));
+      section.page_indices = section
+        .pages
+        .iter()
+        .enumerate()
+        .map(|(index, page)| (*page, index))
+        .collect();
+    }
+
+    // Iterating over a BTreeMap is sorted, so subsections end up sorted too.
+    let paths = sections.keys().cloned().collect::<Vec<_>>();
+    for path in paths {
+      if let Some(parent) = sections[&path].ancestors.last().cloned() {
+        sections.get_mut(&parent).unwrap().subsections.push(path);
+      }
+    }
+
+    sections
+  }
+
+  /// Returns the URL of this [`Section`] relative to the root of the site,
+  /// which is the [`Document::permalink`] of its `index.md` or otherwise the
+  /// path of its directory.
+  pub fn permalink(&self, documents: &[Document]) -> String {
+    if let Some(index) = self.index {
+      return documents[index].permalink.clone();
+    }
+
+    let mut permalink = String::from("/");
+    for component in self.path.iter() {
+      permalink += &component.to_string_lossy();
+      permalink += "/";
+    }
+
+    permalink
+  }
+
+  /// Returns the positions of the pages before and after the page at
+  /// `position` in [`Section::pages`]. These are both `None` for pages that
+  /// aren't in this section, like its `index.md`.
+  pub fn previous_and_next(
+    &self,
+    position: usize,
+  ) -> (Option<usize>, Option<usize>) {
+    match self.page_indices.get(&position).copied() {
+      Some(index) => (
+        index.checked_sub(1).map(|index| self.pages[index]),
+        self.pages.get(index + 1).copied(),
+      ),
+      None => (None, None),
+    }
+  }
+
+  /// Returns the title of this [`Section`], which is the
+  /// [`Metadata::title`](crate::document::Metadata::title) of its `index.md`.
+  pub fn title<'a>(&self, documents: &'a [Document]) -> Option<&'a str> {
+    self
+      .index
+      .and_then(|index| documents[index].metadata.title.as_deref())
+  }
+}
+
+/// The ways the pages of a [`Section`] can be sorted. Sorting always falls
+/// back to the source path of the [`Document`]s, which is also where pages
+/// missing the value being sorted by end up, after all the others.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[non_exhaustive]
+#[serde(rename_all = "lowercase")]
+pub enum SortBy {
+  /// Sort by [`Metadata::date`](crate::document::Metadata::date), newest
+  /// first.
+  Date,
+  /// Sort by the path of the source files.
+  #[default]
+  Path,
+  /// Sort by [`Metadata::title`](crate::document::Metadata::title),
+  /// alphabetically.
+  Title,
+  /// Sort by [`Metadata::weight`](crate::document::Metadata::weight), lowest
+  /// first.
+  Weight,
+}
+
+impl SortBy {
+  /// Compares two [`Document`]s for sorting.
+  pub fn compare(self, a: &Document, b: &Document) -> Ordering {
+    let (a_metadata, b_metadata) = (&a.metadata, &b.metadata);
+    let ordering = match self {
+      Self::Date => compare_present_first(
+        a_metadata.date.map(Reverse),
+        b_metadata.date.map(Reverse),
+      ),
+      Self::Path => Ordering::Equal,
+      Self::Title => compare_present_first(
+        a_metadata.title.as_deref(),
+        b_metadata.title.as_deref(),
+      ),
+      Self::Weight => {
+        compare_present_first(a_metadata.weight, b_metadata.weight)
+      }
+    };
+
+    ordering.then_with(|| a.source_path.cmp(&b.source_path))
+  }
+}
+
+/// Compares two optional values, with `None` coming after every value.
+fn compare_present_first<T: Ord>(a: Option<T>, b: Option<T>) -> Ordering {
+  match (a, b) {
+    (Some(a), Some(b)) => a.cmp(&b),
+    (Some(_), None) => Ordering::Less,
+    (None, Some(_)) => Ordering::Greater,
+    (None, None) => Ordering::Equal,
+  }
+}
+
+/// A reference to a [`Section`] as templates receive it in `ancestors` and
+/// `parent`.
+#[derive(Serialize)]
+struct SectionLink<'a> {
+  path: &'a Path,
+  permalink: String,
+  title: Option<&'a str>,
+  url: String,
+}
+
+/// A reference to a [`Document`] as templates receive it in a section's
+/// `index` and `pages`.
+#[derive(Serialize)]
+struct SectionPage<'a> {
+  metadata: &'a Metadata,
+  permalink: &'a str,
+  section: &'a Path,
+  url: &'a str,
+}
+
+impl<'a> From<&'a Document> for SectionPage<'a> {
+  fn from(document: &'a Document) -> Self {
+    Self {
+      metadata: &document.metadata,
+      permalink: &document.permalink,
+      section: &document.section,
+      url: &document.url,
+    }
+  }
+}
+
+/// A [`Section`] and its subsections as templates receive them, see
+/// [`Section`] for details.
+#[derive(Serialize)]
+struct SectionTree<'a> {
+  index: Option<SectionPage<'a>>,
+  pages: Vec<SectionPage<'a>>,
+  path: &'a Path,
+  permalink: String,
+  sort_by: SortBy,
+  subsections: Vec<SectionTree<'a>>,
+  title: Option<&'a str>,
+  url: String,
+}
+
+/// A [`SectionTree`] with the sections it's inside of, which is what
+/// templates receive as `section`.
+#[derive(Serialize)]
+struct SectionContext<'a> {
+  ancestors: Vec<SectionLink<'a>>,
+  parent: Option<SectionLink<'a>>,
+  #[serde(flatten)]
+  tree: SectionTree<'a>,
+}
+
+/// Creates the template context for every [`Section`], keyed by
+/// [`Section::path`].
+pub(crate) fn section_contexts(
+  launchpad: &Launchpad,
+  sections: &BTreeMap<PathBuf, Section>,
+  documents: &[Document],
+) -> Result<HashMap<PathBuf, Value>> {
+  let link = |path| section_link(launchpad, &sections[path], documents);
+
+  let mut contexts = HashMap::new();
+  for (path, section) in sections {
+    let context = SectionContext {
+      ancestors: section.ancestors.iter().map(link).collect(),
+      parent: section.ancestors.last().map(link),
+      tree: section_tree(launchpad, sections, documents, section),
+    };
+    contexts.insert(path.clone(), serde_json::to_value(context)?);
+  }
+
+  Ok(contexts)
+}
+
+/// Creates the [`SectionLink`] for a [`Section`].
+fn section_link<'a>(
+  launchpad: &Launchpad,
+  section: &'a Section,
+  documents: &'a [Document],
+) -> SectionLink<'a> {
+  let permalink = section.permalink(documents);
+  SectionLink {
+    path: &section.path,
+    title: section.title(documents),
+    url: launchpad.url(&permalink),
+    permalink,
+  }
+}
+
+/// Creates the [`SectionTree`] for a [`Section`] and all of its subsections.
+fn section_tree<'a>(
+  launchpad: &Launchpad,
+  sections: &'a BTreeMap<PathBuf, Section>,
+  documents: &'a [Document],
+  section: &'a Section,
+) -> SectionTree<'a> {
+  let permalink = section.permalink(documents);
+  SectionTree {
+    index: section.index.map(|index| (&documents[index]).into()),
+    pages: section
+      .pages
+      .iter()
+      .map(|page| (&documents[*page]).into())
+      .collect(),
+    path: &section.path,
+    sort_by: section.sort_by,
+    subsections: section
+      .subsections
+      .iter()
+      .map(|path| section_tree(launchpad, sections, documents, &sections[path]))
+      .collect(),
+    title: section.title(documents),
+    url: launchpad.url(&permalink),
+    permalink,
+  }
+}
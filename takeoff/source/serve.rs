@@ -0,0 +1,362 @@
+use std::{
+  ffi::OsStr,
+  fs::read,
+  io::{self, BufRead, BufReader, Write},
+  net::{TcpListener, TcpStream},
+  path::PathBuf,
+  sync::{Arc, Mutex},
+  thread,
+};
+
+use tracing::{debug, instrument, trace};
+
+use crate::{
+  error::{Error, Result},
+  launchpad::Mode,
+  Launchpad,
+};
+
+/// The path the live reload script listens to for reload events.
+pub const LIVE_RELOAD_PATH: &str = "/__takeoff/live-reload";
+
+/// The script injected into HTML responses in [`Mode::Development`]. It
+/// reloads the page whenever the server sends an event after a rebuild.
+const LIVE_RELOAD_SCRIPT: &str = r#"<script>
+new EventSource("/__takeoff/live-reload").onmessage = () => location.reload();
+</script>"#;
+
+impl Launchpad {
+  /// Serves [`Launchpad::output`] over HTTP on `address` (for example
+  /// `"127.0.0.1:8080"`) and then [`watches`](Launchpad::watch) for changes.
+  ///
+  /// In [`Mode::Development`] a small script is injected into every HTML page
+  /// that reloads it after each successful rebuild. The site should already
+  /// have been generated before calling this, and like [`Launchpad::watch`]
+  /// it only returns if setting up the server or watcher fails.
+  #[instrument(skip(self))]
+  pub fn serve(&mut self, address: &str) -> Result<()> {
+    let listener =
+      TcpListener::bind(address).map_err(|source| Error::Server {
+        address: address.to_string(),
+        source,
+      })?;
+    let server = Arc::new(Server {
+      base_path: self.url("/"),
+      clients: Mutex::new(vec![]),
+      live_reload: self.mode == Mode::Development,
+      output: self.output.clone(),
+    });
+
+    let accepting = Arc::clone(&server);
+    thread::spawn(move || {
+      for stream in listener.incoming().filter_map(io::Result::ok) {
+        let server = Arc::clone(&accepting);
+        thread::spawn(move || {
+          if let Err(err) = server.handle(stream) {
+            debug!("Error handling request: {}", err);
+          }
+        });
+      }
+    });
+
+    self.watch_with(|_| server.reload())
+  }
+}
+
+/// The state shared between the connections of [`Launchpad::serve`].
+struct Server {
+  /// The path of [`Launchpad::base_url`], which is removed from requests
+  /// before looking for files.
+  base_path: String,
+  /// The connections waiting for live reload events.
+  clients: Mutex<Vec<TcpStream>>,
+  /// Whether to inject [`LIVE_RELOAD_SCRIPT`] into HTML responses.
+  live_reload: bool,
+  /// The directory to serve files from.
+  output: PathBuf,
+}
+
+impl Server {
+  /// Reads a request from the stream and responds to it.
+  fn handle(&self, mut stream: TcpStream) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    // Skip the headers, nothing in them is needed.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+      header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+      (Some(method), Some(target)) => (method, target),
+      _ => {
+        return respond(&mut stream, "400 Bad Request", "text/plain", b"");
+      }
+    };
+
+    trace!("{} {}", method, target);
+    if method != "GET" {
+      let status = "405 Method Not Allowed";
+      return respond(&mut stream, status, "text/plain", b"");
+    }
+
+    let path = percent_decode(target.split(['?', '#']).next().unwrap());
+    if self.live_reload && path == LIVE_RELOAD_PATH {
+      stream.write_all(
+        b"HTTP/1.1 200 OK\r\n\
+          Content-Type: text/event-stream\r\n\
+          Cache-Control: no-cache\r\n\r\n",
+      )?;
+      self.clients.lock().unwrap().push(stream);
+      return Ok(());
+    }
+
+    let file = match self.resolve(&path) {
+      Some(file) => file,
+      None => {
+        return respond(&mut stream, "404 Not Found", "text/plain", b"404")
+      }
+    };
+
+    let mut body = read(&file)?;
+    let extension = file.extension().and_then(OsStr::to_str);
+    if self.live_reload && extension == Some("html") {
+      body = inject_live_reload(body);
+    }
+
+    respond(&mut stream, "200 OK", content_type(extension), &body)
+  }
+
+  /// Finds the file in [`Server::output`] for a request path, trying
+  /// `index.html` for directories and adding `.html` to paths without an
+  /// extension. Paths are relative to [`Server::base_path`] when they start
+  /// with it.
+  fn resolve(&self, path: &str) -> Option<PathBuf> {
+    // The base path itself is the root, with or without a trailing slash.
+    let base_path = self.base_path.trim_end_matches('/');
+    let relative = match path.strip_prefix(base_path) {
+      Some(rest) if rest.is_empty() || rest.starts_with('/') => rest,
+      _ => path,
+    };
+    let relative = relative.trim_start_matches('/');
+    if relative.split('/').any(|part| part == "..") {
+      return None;
+    }
+
+    let file = self.output.join(relative);
+    let mut candidates = vec![file.clone(), file.join("index.html")];
+    if file.extension().is_none() {
+      candidates.push(file.with_extension("html"));
+    }
+
+    candidates.into_iter().find(|file| file.is_file())
+  }
+
+  /// Sends a reload event to every connected client, forgetting the ones
+  /// that have disconnected.
+  fn reload(&self) {
+    let mut clients = self.clients.lock().unwrap();
+    debug!("Sending reload to {} clients", clients.len());
+    clients.retain(|mut client| client.write_all(b"data: reload\n\n").is_ok());
+  }
+}
+
+/// Writes a complete HTTP response to the stream.
+fn respond(
+  stream: &mut TcpStream,
+  status: &str,
+  content_type: &str,
+  body: &[u8],
+) -> io::Result<()> {
+  write!(
+    stream,
+    "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\
+     Connection: close\r\n\r\n",
+    status,
+    content_type,
+    body.len()
+  )?;
+  stream.write_all(body)?;
+  stream.flush()
+}
+
+/// Inserts [`LIVE_RELOAD_SCRIPT`] before the closing `</body>` tag in any
+/// case, or at the end of the HTML if there is none.
+fn inject_live_reload(html: Vec<u8>) -> Vec<u8> {
+  let html = String::from_utf8_lossy(&html);
+  let index = html
+    .to_ascii_lowercase()
+    .rfind("</body>")
+    .unwrap_or(html.len());
+  let mut injected = html.into_owned();
+  injected.insert_str(index, LIVE_RELOAD_SCRIPT);
+  injected.into_bytes()
+}
+
+/// Decodes `%XX` escapes in a request path.
+fn percent_decode(path: &str) -> String {
+  let bytes = path.as_bytes();
+  let mut decoded = vec![];
+  let mut index = 0;
+  while index < bytes.len() {
+    let escaped = bytes
+      .get(index + 1..index + 3)
+      .and_then(|hex| std::str::from_utf8(hex).ok())
+      .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+    match (bytes[index], escaped) {
+      (b'%', Some(byte)) => {
+        decoded.push(byte);
+        index += 3;
+      }
+      (byte, _) => {
+        decoded.push(byte);
+        index += 1;
+      }
+    }
+  }
+
+  String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Returns the MIME type for common file extensions.
+fn content_type(extension: Option<&str>) -> &'static str {
+  match extension {
+    Some("html") => "text/html; charset=utf-8",
+    Some("css") => "text/css; charset=utf-8",
+    Some("js") => "text/javascript; charset=utf-8",
+    Some("json") => "application/json",
+    Some("xml") => "application/xml",
+    Some("txt") => "text/plain; charset=utf-8",
+    Some("svg") => "image/svg+xml",
+    Some("png") => "image/png",
+    Some("jpg") | Some("jpeg") => "image/jpeg",
+    Some("gif") => "image/gif",
+    Some("webp") => "image/webp",
+    Some("ico") => "image/x-icon",
+    Some("woff") => "font/woff",
+    Some("woff2") => "font/woff2",
+    _ => "application/octet-stream",
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    env::temp_dir,
+    fs::{create_dir_all, write},
+    process,
+  };
+
+  use super::*;
+
+  /// Creates a [`Server`] for an output directory with a few pages, and a
+  /// `secret.txt` next to that directory that must never be served.
+  fn server(name: &str, base_path: &str) -> Server {
+    let directory =
+      temp_dir().join(format!("takeoff-test-serve-{}-{}", name, process::id()));
+    let output = directory.join("public");
+    create_dir_all(output.join("blog")).unwrap();
+    for file in ["index.html", "about.html", "blog/index.html", "style.css"] {
+      write(output.join(file), file).unwrap();
+    }
+    write(directory.join("secret.txt"), "secret").unwrap();
+
+    Server {
+      base_path: base_path.to_string(),
+      clients: Mutex::new(vec![]),
+      live_reload: true,
+      output,
+    }
+  }
+
+  /// Resolves a request target like [`Server::handle`] does, returning the
+  /// file relative to [`Server::output`].
+  fn resolve(server: &Server, target: &str) -> Option<String> {
+    let file = server.resolve(&percent_decode(target))?;
+    let relative = file.strip_prefix(&server.output).unwrap();
+    Some(relative.to_string_lossy().replace('\\', "/"))
+  }
+
+  #[test]
+  fn test_resolve() {
+    let server = server("root", "/");
+    let resolve = |target| resolve(&server, target);
+    assert_eq!(resolve("/").as_deref(), Some("index.html"));
+    assert_eq!(resolve("/about").as_deref(), Some("about.html"));
+    assert_eq!(resolve("/about.html").as_deref(), Some("about.html"));
+    assert_eq!(resolve("/blog").as_deref(), Some("blog/index.html"));
+    assert_eq!(resolve("/blog/").as_deref(), Some("blog/index.html"));
+    assert_eq!(resolve("/style.css").as_deref(), Some("style.css"));
+    assert_eq!(resolve("/missing"), None);
+  }
+
+  #[test]
+  fn test_resolve_base_path() {
+    let server = server("base-path", "/docs/v2/");
+    let resolve = |target| resolve(&server, target);
+    assert_eq!(resolve("/docs/v2/").as_deref(), Some("index.html"));
+    assert_eq!(resolve("/docs/v2").as_deref(), Some("index.html"));
+    assert_eq!(resolve("/docs/v2/about").as_deref(), Some("about.html"));
+    assert_eq!(
+      resolve("/docs/v2/blog/").as_deref(),
+      Some("blog/index.html")
+    );
+    // Only whole directories are stripped.
+    assert_eq!(resolve("/docs/v2about"), None);
+  }
+
+  #[test]
+  fn test_resolve_traversal() {
+    for base_path in ["/", "/docs/"] {
+      let server = server("traversal", base_path);
+      for target in [
+        "/../secret.txt",
+        "/docs/../secret.txt",
+        "/blog/../../secret.txt",
+        "/%2e%2e/secret.txt",
+        "/%2E%2E%2Fsecret.txt",
+        "/docs/..%2fsecret.txt",
+        "/blog/%2e%2e/%2e%2e/secret.txt",
+      ] {
+        assert_eq!(resolve(&server, target), None, "{}", target);
+      }
+    }
+  }
+
+  #[test]
+  fn test_inject_live_reload() {
+    let inject = |html: &str| {
+      String::from_utf8(inject_live_reload(html.as_bytes().to_vec())).unwrap()
+    };
+
+    assert_eq!(
+      inject("<body><p>Hi</p></body></html>"),
+      format!("<body><p>Hi</p>{}</body></html>", LIVE_RELOAD_SCRIPT)
+    );
+    assert_eq!(
+      inject("<BODY>Hi</BODY>"),
+      format!("<BODY>Hi{}</BODY>", LIVE_RELOAD_SCRIPT)
+    );
+    // Only the last closing tag counts, earlier ones can be in comments.
+    assert_eq!(
+      inject("<!-- </body> --></body>"),
+      format!("<!-- </body> -->{}</body>", LIVE_RELOAD_SCRIPT)
+    );
+    assert_eq!(
+      inject("<p>Hi</p>"),
+      format!("<p>Hi</p>{}", LIVE_RELOAD_SCRIPT)
+    );
+  }
+
+  #[test]
+  fn test_percent_decode() {
+    assert_eq!(percent_decode("/%2e%2e/a%20b"), "/../a b");
+    assert_eq!(percent_decode("/caf%C3%A9"), "/café");
+    assert_eq!(percent_decode("/100%"), "/100%");
+    assert_eq!(percent_decode("/%zz%2"), "/%zz%2");
+  }
+}
@@ -0,0 +1,341 @@
+use std::ops::Range;
+
+use pulldown_cmark::{Event, Options, Parser, Tag};
+use serde_json::{Map, Number, Value};
+use tera::{Context, Tera};
+
+use crate::error::{Error, Result};
+
+/// The directory in
+/// [`Templates::directory`](crate::launchpad::Templates::directory) that
+/// shortcode templates are in.
+pub const SHORTCODES_DIRECTORY: &str = "shortcodes";
+
+/// Renders the shortcodes in some Markdown with their templates, before the
+/// Markdown itself is rendered.
+///
+/// A shortcode calls the template `shortcodes/<name>.html` in
+/// [`Templates::directory`](crate::launchpad::Templates::directory) with
+/// named arguments, and its output replaces the shortcode in the Markdown.
+/// There are two forms:
+///
+/// * Inline: `{{ youtube(id="dQw4w9WgXcQ", autoplay=false) }}`.
+/// * Block: `{% note(title="Heads up") %}` followed by some content and
+///   `{% end %}`. The content is passed to the template as `body`, which can
+///   be rendered with `body | render_markdown`. Inline shortcodes in the
+///   content are rendered first, but blocks can't be nested.
+///
+/// The arguments are available as variables in the template. Values can be
+/// strings in single or double quotes, numbers, `true` and `false`, and
+/// arrays of those in square brackets. Parentheses are required, even when
+/// there are no arguments.
+///
+/// Shortcodes in code blocks and inline code are left as they are, so they can
+/// be documented. This includes indented code blocks and code blocks inside
+/// blockquotes and lists, since [`pulldown_cmark`] is used to find them.
+pub fn render_shortcodes(source: &str, tera: &Tera) -> Result<String> {
+  let mut output = String::with_capacity(source.len());
+  let code = code_ranges(source);
+  let mut code = code.iter().peekable();
+  let mut position = 0;
+
+  while position < source.len() {
+    // Code is copied as a whole.
+    while code.next_if(|range| range.end <= position).is_some() {}
+    if let Some(range) = code.next_if(|range| range.start <= position) {
+      output.push_str(&source[position..range.end]);
+      position = range.end;
+      continue;
+    }
+
+    let rest = &source[position..];
+    let character = rest.chars().next().unwrap();
+
+    if !rest.starts_with("{{") && !rest.starts_with("{%") {
+      output.push(character);
+      position += character.len_utf8();
+      continue;
+    }
+
+    let call = match Call::parse(rest) {
+      Some(call) => call,
+      None => {
+        output.push_str(&rest[..2]);
+        position += 2;
+        continue;
+      }
+    };
+    let line = source[..position].matches('\n').count() + 1;
+    let Call {
+      arguments,
+      is_block,
+      mut length,
+      name,
+    } = call.map_err(|message| shortcode_error(line, message))?;
+
+    let mut context = Context::from_value(Value::Object(arguments))
+      .expect("Shortcode arguments are always an object");
+    if is_block {
+      let body_start = &rest[length..];
+      let end = END_PATTERN
+        .iter()
+        .filter_map(|pattern| body_start.find(pattern).map(|at| (at, pattern)))
+        .min();
+      let (body_length, end) = end.ok_or_else(|| {
+        shortcode_error(
+          line,
+          format!("Missing `{{% end %}}` for shortcode `{}`", name),
+        )
+      })?;
+
+      let body = &body_start[..body_length];
+      let body = render_shortcodes(body, tera).map_err(|err| match err {
+        Error::Shortcode {
+          line: inner,
+          message,
+          ..
+        } => shortcode_error(line + inner - 1, message),
+        err => err,
+      })?;
+      context.insert("body", &body);
+      length += body_length + end.len();
+    }
+
+    let name = format!("{}/{}.html", SHORTCODES_DIRECTORY, name);
+    let html =
+      tera
+        .render(&name, &context)
+        .map_err(|source| Error::Template {
+          document: None,
+          name: Some(name.clone()),
+          source,
+        })?;
+    output.push_str(&html);
+    position += length;
+  }
+
+  Ok(output)
+}
+
+/// The ways a block shortcode can be closed, to allow for the same
+/// whitespace as Tera does.
+const END_PATTERN: [&str; 4] = ["{% end %}", "{%end%}", "{% end%}", "{%end %}"];
+
+/// Creates an [`Error::Shortcode`] without a path.
+fn shortcode_error(line: usize, message: String) -> Error {
+  Error::Shortcode {
+    line,
+    message,
+    path: None,
+  }
+}
+
+/// Returns the byte ranges of the code blocks and inline code in some
+/// Markdown, in order.
+fn code_ranges(source: &str) -> Vec<Range<usize>> {
+  Parser::new_ext(source, Options::all())
+    .into_offset_iter()
+    .filter_map(|(event, range)| match event {
+      Event::Start(Tag::CodeBlock(_)) | Event::Code(_) => Some(range),
+      _ => None,
+    })
+    .collect()
+}
+
+/// A shortcode in the Markdown.
+struct Call {
+  /// The arguments of the shortcode.
+  arguments: Map<String, Value>,
+  /// Whether this is a block shortcode that needs an end.
+  is_block: bool,
+  /// The length of the shortcode in the Markdown, without the body and end
+  /// of block shortcodes.
+  length: usize,
+  /// The name of the shortcode.
+  name: String,
+}
+
+impl Call {
+  /// Parses the shortcode at the start of `source`, which starts with `{{` or
+  /// `{%`. Returns `None` when it isn't a shortcode at all and an error
+  /// message when it looks like one but is invalid.
+  fn parse(source: &str) -> Option<Result<Self, String>> {
+    let is_block = source.starts_with("{%");
+    let mut parser = ArgumentParser {
+      position: 2,
+      source,
+    };
+
+    parser.skip_whitespace();
+    let name = parser.identifier()?;
+    parser.skip_whitespace();
+    if !parser.eat('(') {
+      return None;
+    }
+
+    Some(parser.arguments().and_then(|arguments| {
+      parser.skip_whitespace();
+      let close = if is_block { "%}" } else { "}}" };
+      if !parser.rest().starts_with(close) {
+        return Err(format!(
+          "Expected `{}` after the arguments of shortcode `{}`",
+          close, name
+        ));
+      }
+
+      Ok(Self {
+        arguments,
+        is_block,
+        length: parser.position + close.len(),
+        name: name.to_string(),
+      })
+    }))
+  }
+}
+
+/// Parses the arguments of a [`Call`].
+struct ArgumentParser<'a> {
+  /// How far the parser is in `source`.
+  position: usize,
+  /// The shortcode being parsed.
+  source: &'a str,
+}
+
+impl<'a> ArgumentParser<'a> {
+  /// Returns the part of the source that hasn't been parsed yet.
+  fn rest(&self) -> &'a str {
+    &self.source[self.position..]
+  }
+
+  /// Moves past `character` if it's next.
+  fn eat(&mut self, character: char) -> bool {
+    let eaten = self.rest().starts_with(character);
+    if eaten {
+      self.position += character.len_utf8();
+    }
+
+    eaten
+  }
+
+  /// Moves past any whitespace, including newlines.
+  fn skip_whitespace(&mut self) {
+    let rest = self.rest();
+    self.position += rest.len() - rest.trim_start().len();
+  }
+
+  /// Parses a name made of ASCII letters, digits and underscores that
+  /// doesn't start with a digit.
+  fn identifier(&mut self) -> Option<&'a str> {
+    let rest = self.rest();
+    let length = rest
+      .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+      .unwrap_or(rest.len());
+    if length == 0 || rest.starts_with(|c: char| c.is_ascii_digit()) {
+      return None;
+    }
+
+    self.position += length;
+    Some(&rest[..length])
+  }
+
+  /// Parses `key=value` pairs separated by commas, up to and including the
+  /// closing parenthesis.
+  fn arguments(&mut self) -> Result<Map<String, Value>, String> {
+    let mut arguments = Map::new();
+    loop {
+      self.skip_whitespace();
+      if self.eat(')') {
+        return Ok(arguments);
+      }
+
+      let key = self
+        .identifier()
+        .ok_or_else(|| "Expected an argument name".to_string())?;
+      self.skip_whitespace();
+      if !self.eat('=') {
+        return Err(format!("Expected `=` after argument `{}`", key));
+      }
+
+      self.skip_whitespace();
+      let value = self.value()?;
+      arguments.insert(key.to_string(), value);
+
+      self.skip_whitespace();
+      if !self.eat(',') && !self.rest().starts_with(')') {
+        return Err(format!("Expected `,` or `)` after argument `{}`", key));
+      }
+    }
+  }
+
+  /// Parses a string, number, boolean or array.
+  fn value(&mut self) -> Result<Value, String> {
+    let rest = self.rest();
+    if let Some(quote) = rest.chars().next().filter(|c| matches!(c, '"' | '\''))
+    {
+      return self.string(quote).map(Value::String);
+    }
+
+    if self.eat('[') {
+      let mut values = vec![];
+      loop {
+        self.skip_whitespace();
+        if self.eat(']') {
+          return Ok(Value::Array(values));
+        }
+
+        values.push(self.value()?);
+        self.skip_whitespace();
+        if !self.eat(',') && !self.rest().starts_with(']') {
+          return Err("Expected `,` or `]` in array".to_string());
+        }
+      }
+    }
+
+    let length = rest
+      .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '.')))
+      .unwrap_or(rest.len());
+    let word = &rest[..length];
+    let value = match word {
+      "true" => Value::Bool(true),
+      "false" => Value::Bool(false),
+      _ => {
+        let number = match word.parse::<i64>() {
+          Ok(integer) => Some(Number::from(integer)),
+          Err(_) => word.parse::<f64>().ok().and_then(Number::from_f64),
+        };
+        match number {
+          Some(number) => Value::Number(number),
+          None if word.is_empty() => {
+            return Err("Expected an argument value".to_string())
+          }
+          None => return Err(format!("Invalid argument value `{}`", word)),
+        }
+      }
+    };
+
+    self.position += length;
+    Ok(value)
+  }
+
+  /// Parses a string in `quote`s, where a backslash escapes the next
+  /// character.
+  fn string(&mut self, quote: char) -> Result<String, String> {
+    let mut value = String::new();
+    let mut characters = self.rest().char_indices().skip(1);
+    while let Some((index, character)) = characters.next() {
+      match character {
+        '\\' => match characters.next() {
+          Some((_, escaped)) => value.push(escaped),
+          None => break,
+        },
+        _ if character == quote => {
+          self.position += index + character.len_utf8();
+          return Ok(value);
+        }
+        _ => value.push(character),
+      }
+    }
+
+    Err("Unclosed string in shortcode arguments".to_string())
+  }
+}
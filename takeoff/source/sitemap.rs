@@ -0,0 +1,109 @@
+use std::{fs::metadata, path::PathBuf};
+
+use chrono::{DateTime, SubsecRound, Utc};
+use serde::Serialize;
+use serde_json::json;
+
+use crate::{
+  document::{Date, Document},
+  launchpad::GeneratedPage,
+  Launchpad,
+};
+
+/// The file name of the sitemap and of the template it's rendered with.
+pub const SITEMAP_FILE_NAME: &str = "sitemap.xml";
+
+/// The file name of the `robots.txt` and of the template it's rendered with.
+pub const ROBOTS_FILE_NAME: &str = "robots.txt";
+
+/// The built-in template for the sitemap.
+pub(crate) const SITEMAP_TEMPLATE: &str = include_str!("sitemap/sitemap.xml");
+
+/// The built-in template for the `robots.txt`.
+pub(crate) const ROBOTS_TEMPLATE: &str = include_str!("sitemap/robots.txt");
+
+/// A page in the sitemap as templates receive it in `sitemap.entries`.
+#[derive(Debug, Serialize)]
+#[non_exhaustive]
+pub struct SitemapEntry<'a> {
+  /// When the page was last changed, which is the
+  /// [`Metadata::updated`](crate::document::Metadata::updated) or
+  /// [`Metadata::date`](crate::document::Metadata::date) of its [`Document`],
+  /// or otherwise the modification time of its source file.
+  pub lastmod: Option<Date>,
+  /// The same as [`Document::permalink`].
+  pub permalink: &'a str,
+  /// The absolute URL of the page, see [`Launchpad::absolute_url`].
+  pub url: String,
+}
+
+impl<'a> SitemapEntry<'a> {
+  /// Creates the [`SitemapEntry`] for a [`Document`].
+  pub fn new(launchpad: &Launchpad, document: &'a Document) -> Self {
+    let metadata = &document.metadata;
+    Self {
+      lastmod: metadata
+        .updated
+        .or(metadata.date)
+        .or_else(|| modified(document)),
+      permalink: &document.permalink,
+      url: launchpad.absolute_url(&document.permalink),
+    }
+  }
+}
+
+/// Returns the modification time of the source file of a [`Document`].
+fn modified(document: &Document) -> Option<Date> {
+  let modified = metadata(&document.source_path)
+    .and_then(|metadata| metadata.modified())
+    .ok()?;
+  let modified = DateTime::<Utc>::from(modified).trunc_subsecs(0);
+  Some(Date(modified.into()))
+}
+
+impl Launchpad {
+  /// Creates the sitemap and `robots.txt` when [`Launchpad::sitemap`] and
+  /// [`Launchpad::robots_txt`] are enabled.
+  ///
+  /// The sitemap lists every [`Document`] that isn't a draft, also in
+  /// [`Mode::Development`](crate::launchpad::Mode::Development) where drafts
+  /// are rendered, so a development build never advertises them.
+  pub(crate) fn sitemap_pages(
+    &self,
+    documents: &[Document],
+  ) -> Vec<GeneratedPage> {
+    let sitemap_url = self.absolute_url(&format!("/{}", SITEMAP_FILE_NAME));
+
+    let mut pages = vec![];
+    if self.sitemap {
+      let entries = documents
+        .iter()
+        .filter(|document| !document.metadata.is_draft)
+        .map(|document| SitemapEntry::new(self, document))
+        .collect::<Vec<_>>();
+
+      pages.push(GeneratedPage {
+        context: json!({
+          "sitemap": {
+            "entries": entries,
+            "url": sitemap_url,
+          },
+        }),
+        output_path: PathBuf::from(SITEMAP_FILE_NAME),
+        template: SITEMAP_FILE_NAME.to_string(),
+      });
+    }
+
+    if self.robots_txt {
+      pages.push(GeneratedPage {
+        context: json!({
+          "sitemap_url": self.sitemap.then_some(&sitemap_url),
+        }),
+        output_path: PathBuf::from(ROBOTS_FILE_NAME),
+        template: ROBOTS_FILE_NAME.to_string(),
+      });
+    }
+
+    pages
+  }
+}
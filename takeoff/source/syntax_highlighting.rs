@@ -1,8 +1,16 @@
+use once_cell::sync::Lazy;
 use syntect::{
   html::{ClassStyle, ClassedHTMLGenerator},
   parsing::{SyntaxReference, SyntaxSet},
+  util::LinesWithEndings,
 };
 
+/// The default [`SyntaxSet`] from [`syntect`]. Loading it is expensive, so
+/// it's only done once, the first time it's used, and then shared by the
+/// Markdown renderer and all the Tera filters.
+pub static SYNTAX_SET: Lazy<SyntaxSet> =
+  Lazy::new(SyntaxSet::load_defaults_newlines);
+
 /// Highlights some code with [`syntect`]. See the source code for
 /// [`tera_highlight_code`](crate::templating::tera_highlight_code)
 /// for an example.
@@ -12,13 +20,13 @@ pub fn highlight_code(
   syntax_set: &SyntaxSet,
 ) -> String {
   let mut generator = ClassedHTMLGenerator::new_with_class_style(
-    &syntax,
-    &syntax_set,
+    syntax,
+    syntax_set,
     ClassStyle::SpacedPrefixed { prefix: "code-" },
   );
 
-  for line in source.lines() {
-    generator.parse_html_for_line(line);
+  for line in LinesWithEndings::from(source) {
+    generator.parse_html_for_line_which_includes_newline(line);
   }
 
   generator.finalize()
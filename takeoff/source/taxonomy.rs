@@ -0,0 +1,253 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use slug::slugify;
+use tracing::trace;
+
+use crate::{
+  document::Document,
+  error::{Error, Result},
+  launchpad::GeneratedPage,
+  pagination::{page_output_path, paginate},
+  section::SortBy,
+  Launchpad,
+};
+
+/// A [`Taxonomy`] groups [`Document`]s by the terms they list for it in
+/// [`Metadata::taxonomies`](crate::document::Metadata::taxonomies), like tags
+/// or categories. Taxonomies are configured in [`Launchpad::taxonomies`].
+///
+/// For every taxonomy an index page listing all of its terms is generated
+/// with the `<name>/list.html` template, and a page for every term listing
+/// its documents with the `<name>/term.html` template. Both templates are
+/// relative to [`Templates::directory`](crate::launchpad::Templates) and are
+/// written to `<name>/index.html` and `<name>/<term slug>/index.html` in
+/// [`Launchpad::output`].
+///
+/// Both templates receive `taxonomy`, with its `name`, `permalink` and `url`.
+/// On the index page it also has `terms`, and on term pages the term is
+/// available as `term`. Terms have a `name`, `pages`, `permalink`, `slug` and
+/// `url`. When [`Taxonomy::paginate_by`] is set, term pages also receive a
+/// `paginator`. Every template can link to these pages with the
+/// `taxonomy_url` function, see
+/// [`tera_taxonomy_url`](crate::templating::tera_taxonomy_url).
+#[derive(Debug, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct Taxonomy {
+  /// The name of the taxonomy, which is the key documents use in their
+  /// [`Metadata::taxonomies`](crate::document::Metadata::taxonomies) and the
+  /// directory its pages are written to. It has to be a slug, like `tags`.
+  pub name: String,
+  /// Splits the term pages into pages of this many [`Document`]s, see
+  /// [`Paginator`](crate::pagination::Paginator) for details.
+  ///
+  /// Defaults to `None`, which lists every document on a single page.
+  #[serde(default)]
+  pub paginate_by: Option<usize>,
+  /// How the [`Term::pages`] are sorted.
+  ///
+  /// Defaults to [`SortBy::Date`].
+  #[serde(default = "Taxonomy::sort_by_default")]
+  pub sort_by: SortBy,
+}
+
+impl Taxonomy {
+  /// Creates a new [`Taxonomy`] with the default settings.
+  pub fn new(name: &str) -> Self {
+    Self {
+      name: name.to_string(),
+      paginate_by: None,
+      sort_by: Self::sort_by_default(),
+    }
+  }
+
+  pub(crate) fn sort_by_default() -> SortBy {
+    SortBy::Date
+  }
+
+  /// Collects the [`Term`]s of this [`Taxonomy`] from [`Document`]s, sorted
+  /// by their slug. Terms that have the same slug are combined into one,
+  /// which uses the name that came first.
+  pub fn terms(&self, documents: &[Document]) -> Vec<Term> {
+    let mut terms = BTreeMap::new();
+    for (position, document) in documents.iter().enumerate() {
+      let names = match document.metadata.taxonomies.get(&self.name) {
+        Some(names) => names,
+        None => continue,
+      };
+
+      for name in names {
+        let slug = slugify(name);
+        if slug.is_empty() {
+          trace!("Skipping term {:?} without a slug", name);
+          continue;
+        }
+
+        let term = terms.entry(slug.clone()).or_insert_with(|| Term {
+          name: name.clone(),
+          pages: vec![],
+          slug,
+        });
+
+        if term.pages.last() != Some(&position) {
+          term.pages.push(position);
+        }
+      }
+    }
+
+    let mut terms = terms.into_values().collect::<Vec<_>>();
+    for term in &mut terms {
+      term
+        .pages
+        .sort_by(|a, b| self.sort_by.compare(&documents[*a], &documents[*b]));
+    }
+
+    terms
+  }
+
+  /// Returns the URL of the index page of this [`Taxonomy`] relative to the
+  /// root of the site.
+  pub fn permalink(&self) -> String {
+    format!("/{}/", self.name)
+  }
+
+  /// Returns the URL of the page of a [`Term`] relative to the root of the
+  /// site.
+  pub fn term_permalink(&self, term: &Term) -> String {
+    self.slug_permalink(&term.slug)
+  }
+
+  /// Returns the URL of the page of the [`Term`] with `slug` relative to the
+  /// root of the site.
+  pub(crate) fn slug_permalink(&self, slug: &str) -> String {
+    format!("/{}/{}/", self.name, slug)
+  }
+
+  /// Checks that the name of this [`Taxonomy`] can be used as a directory in
+  /// URLs, which means it has to be the same as its slug.
+  pub(crate) fn validate(&self) -> Result<()> {
+    let slug = slugify(&self.name);
+    if self.name.is_empty() || self.name != slug {
+      return Err(Error::Taxonomy(format!(
+        "Invalid taxonomy name {:?}, use a slug like {:?} instead",
+        self.name, slug
+      )));
+    }
+
+    Ok(())
+  }
+
+  /// Creates the index page and the term pages of this [`Taxonomy`].
+  pub(crate) fn pages(
+    &self,
+    launchpad: &Launchpad,
+    documents: &[Document],
+  ) -> Vec<GeneratedPage> {
+    let directory = PathBuf::from(&self.name);
+    let terms = self.terms(documents);
+    let link = TaxonomyLink {
+      name: &self.name,
+      permalink: self.permalink(),
+      url: launchpad.url(&self.permalink()),
+    };
+
+    let term_contexts = terms
+      .iter()
+      .map(|term| {
+        let permalink = self.term_permalink(term);
+        TermContext {
+          name: &term.name,
+          pages: term.pages.iter().map(|page| &documents[*page]).collect(),
+          slug: &term.slug,
+          url: launchpad.url(&permalink),
+          permalink,
+        }
+      })
+      .collect::<Vec<_>>();
+
+    let mut pages = vec![];
+    for term in &term_contexts {
+      let output_path = directory.join(term.slug).join("index.html");
+      let paginate_by = match self.paginate_by {
+        Some(paginate_by) => paginate_by,
+        None => {
+          pages.push(GeneratedPage {
+            context: json!({ "taxonomy": link, "term": term }),
+            output_path,
+            template: format!("{}/term.html", self.name),
+          });
+          continue;
+        }
+      };
+
+      for paginator in
+        paginate(launchpad, &term.pages, paginate_by, &term.permalink)
+      {
+        pages.push(GeneratedPage {
+          context: json!({
+            "paginator": paginator,
+            "taxonomy": link,
+            "term": term,
+          }),
+          output_path: page_output_path(&output_path, paginator.current_index),
+          template: format!("{}/term.html", self.name),
+        });
+      }
+    }
+
+    pages.push(GeneratedPage {
+      context: json!({
+        "taxonomy": TaxonomyContext {
+          link,
+          terms: term_contexts,
+        },
+      }),
+      output_path: directory.join("index.html"),
+      template: format!("{}/list.html", self.name),
+    });
+
+    pages
+  }
+}
+
+/// A term of a [`Taxonomy`] and the [`Document`]s that list it.
+#[derive(Debug, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct Term {
+  /// The name of the term as it was written in the first [`Document`] that
+  /// listed it.
+  pub name: String,
+  /// The positions of the [`Document`]s that list this term, in the
+  /// documents the terms were collected from. They are sorted according to
+  /// [`Taxonomy::sort_by`].
+  pub pages: Vec<usize>,
+  /// The slug of the name, which is used in the term's URL.
+  pub slug: String,
+}
+
+/// A [`Taxonomy`] as templates receive it on term pages.
+#[derive(Serialize)]
+struct TaxonomyLink<'a> {
+  name: &'a str,
+  permalink: String,
+  url: String,
+}
+
+/// A [`Taxonomy`] as templates receive it on its index page.
+#[derive(Serialize)]
+struct TaxonomyContext<'a> {
+  #[serde(flatten)]
+  link: TaxonomyLink<'a>,
+  terms: Vec<TermContext<'a>>,
+}
+
+/// A [`Term`] as templates receive it.
+#[derive(Serialize)]
+struct TermContext<'a> {
+  name: &'a str,
+  pages: Vec<&'a Document>,
+  permalink: String,
+  slug: &'a str,
+  url: String,
+}
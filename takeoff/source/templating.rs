@@ -1,26 +1,35 @@
 use std::collections::HashMap;
 
-use syntect::parsing::SyntaxSet;
-use tera::{Result, Value};
+use slug::slugify;
+use tera::{Error, Filter, Function, Result, Tera, Value};
 
-use crate::{markdown::render_markdown, syntax_highlighting::highlight_code};
+use crate::{
+  feed::{ATOM_FILE_NAME, ATOM_TEMPLATE, RSS_FILE_NAME, RSS_TEMPLATE},
+  markdown::{render_markdown_parts, Admonitions, MarkdownOptions},
+  sitemap::{
+    ROBOTS_FILE_NAME, ROBOTS_TEMPLATE, SITEMAP_FILE_NAME, SITEMAP_TEMPLATE,
+  },
+  syntax_highlighting::{highlight_code, SYNTAX_SET},
+  taxonomy::Taxonomy,
+  url::{absolute_url, relative_url},
+};
 
 /// A filter for Tera that stringifies something to JSON, adds syntax
 /// highlighting and returns it inside a HTML code block. Use with Tera's `safe`
 /// filter to render HTML: `json_dump(document) | safe`.
-pub fn tera_json_dump<'r, 's>(
-  input: &'r Value,
-  _args: &'s HashMap<String, Value>,
+pub fn tera_json_dump(
+  input: &Value,
+  _args: &HashMap<String, Value>,
 ) -> Result<Value> {
   let stringified = serde_json::to_string_pretty(input)?;
 
-  let syntax_set = SyntaxSet::load_defaults_newlines();
+  let syntax_set = &*SYNTAX_SET;
 
   let syntax = syntax_set
     .find_syntax_by_name("JSON")
     .unwrap_or_else(|| syntax_set.find_syntax_plain_text());
 
-  Ok(highlight_code(&stringified, &syntax, &syntax_set).into())
+  Ok(highlight_code(&stringified, syntax, syntax_set).into())
 }
 
 /// A filter for Tera to apply syntax highlighting to a string of code.
@@ -29,9 +38,9 @@ pub fn tera_json_dump<'r, 's>(
 /// use, otherwise `plaintext` is used instead.
 ///
 /// This filter can fail if the input is not a string.
-pub fn tera_highlight_code<'r, 's>(
-  input: &'r Value,
-  args: &'s HashMap<String, Value>,
+pub fn tera_highlight_code(
+  input: &Value,
+  args: &HashMap<String, Value>,
 ) -> Result<Value> {
   let source = input.as_str().expect("Expected input to be a String");
 
@@ -40,23 +49,147 @@ pub fn tera_highlight_code<'r, 's>(
     .and_then(Value::as_str)
     .unwrap_or_default();
 
-  let syntax_set = SyntaxSet::load_defaults_newlines();
+  let syntax_set = &*SYNTAX_SET;
 
   let syntax = syntax_set
-    .find_syntax_by_token(&language)
+    .find_syntax_by_token(language)
     .unwrap_or_else(|| syntax_set.find_syntax_plain_text());
 
-  Ok(highlight_code(&source, &syntax, &syntax_set).into())
+  Ok(highlight_code(source, syntax, syntax_set).into())
 }
 
-/// A filter for Tera to render some Markdown to HTML.
+/// Creates a filter for Tera to render some Markdown to HTML, with the same
+/// `base_url`, [`Admonitions`] and heading anchors as the Markdown of
+/// documents. See [`render_markdown_parts`] for details. Links to `.md` files
+/// are left as they are, since there's no document to resolve them from.
 ///
 /// This filter can fail if the input is not a string.
-pub fn tera_render_markdown<'r, 's>(
-  input: &'r Value,
-  _: &'s HashMap<String, Value>,
-) -> Result<Value> {
-  let source = input.as_str().expect("Expected input to be a String");
+pub fn tera_render_markdown(
+  base_url: &str,
+  admonitions: &Admonitions,
+  heading_anchors: bool,
+) -> impl Filter {
+  let admonitions = admonitions.clone();
+  let base_url = base_url.to_string();
+  move |input: &Value, _: &HashMap<String, Value>| -> Result<Value> {
+    let source = input.as_str().expect("Expected input to be a String");
+    let options = MarkdownOptions {
+      admonitions: admonitions.enabled.then_some(&admonitions),
+      base_url: &base_url,
+      heading_anchors,
+      ..MarkdownOptions::default()
+    };
+
+    Ok(render_markdown_parts(source, &options).html.into())
+  }
+}
+
+/// Creates a function for Tera that returns the root-relative URL for a path,
+/// with the path of `base_url` in front of it: `url_for(path="/blog/")`. See
+/// [`relative_url`] for details.
+///
+/// This function fails if the `path` argument is missing or not a string.
+pub fn tera_url_for(base_url: &str) -> impl Function {
+  let base_url = base_url.to_string();
+  move |args: &HashMap<String, Value>| -> Result<Value> {
+    let path = path_argument("url_for", args)?;
+    Ok(relative_url(&base_url, path).into())
+  }
+}
+
+/// Creates a function for Tera that returns the absolute URL for a path, with
+/// `base_url` in front of it: `absolute_url(path="/blog/")`. See
+/// [`absolute_url`] for details.
+///
+/// This function fails if the `path` argument is missing or not a string.
+pub fn tera_absolute_url(base_url: &str) -> impl Function {
+  let base_url = base_url.to_string();
+  move |args: &HashMap<String, Value>| -> Result<Value> {
+    let path = path_argument("absolute_url", args)?;
+    Ok(absolute_url(&base_url, path).into())
+  }
+}
+
+/// Creates a function for Tera that returns the root-relative URL of the index
+/// page of a [`Taxonomy`], or of one of its terms when `term` is given:
+/// `taxonomy_url(kind="tags", term="Static Sites")`. The term is turned into
+/// its slug, so the names documents list in
+/// [`Metadata::taxonomies`](crate::document::Metadata::taxonomies) can be
+/// passed as they are.
+///
+/// This function fails if the `kind` argument is missing, not a string or not
+/// the name of one of the `taxonomies`, or if `term` isn't a string with a
+/// slug.
+pub fn tera_taxonomy_url(
+  base_url: &str,
+  taxonomies: &[Taxonomy],
+) -> impl Function {
+  let base_url = base_url.to_string();
+  let names = taxonomies
+    .iter()
+    .map(|taxonomy| taxonomy.name.clone())
+    .collect::<Vec<_>>();
+  move |args: &HashMap<String, Value>| -> Result<Value> {
+    let kind = string_argument("taxonomy_url", "kind", args)?;
+    if !names.iter().any(|name| name == kind) {
+      return Err(Error::msg(format!(
+        "taxonomy_url: {:?} is not a taxonomy",
+        kind
+      )));
+    }
+
+    let taxonomy = Taxonomy::new(kind);
+    let permalink = match args.get("term") {
+      Some(_) => {
+        let term = string_argument("taxonomy_url", "term", args)?;
+        let slug = slugify(term);
+        if slug.is_empty() {
+          return Err(Error::msg(format!(
+            "taxonomy_url: term {:?} has no slug",
+            term
+          )));
+        }
+
+        taxonomy.slug_permalink(&slug)
+      }
+      None => taxonomy.permalink(),
+    };
+
+    Ok(relative_url(&base_url, &permalink).into())
+  }
+}
+
+/// Creates a [`Tera`] instance with the templates Takeoff comes with, like
+/// the ones for feeds and the sitemap. The templates from
+/// [`Templates::directory`](crate::launchpad::Templates::directory) are
+/// extended with these, so a template with the same name there replaces the
+/// built-in one.
+pub(crate) fn builtin_templates() -> Result<Tera> {
+  let mut tera = Tera::default();
+  tera.add_raw_templates(vec![
+    (ATOM_FILE_NAME, ATOM_TEMPLATE),
+    (ROBOTS_FILE_NAME, ROBOTS_TEMPLATE),
+    (RSS_FILE_NAME, RSS_TEMPLATE),
+    (SITEMAP_FILE_NAME, SITEMAP_TEMPLATE),
+  ])?;
+  Ok(tera)
+}
+
+/// Gets the `path` argument of a function as a string.
+fn path_argument<'a>(
+  function: &str,
+  args: &'a HashMap<String, Value>,
+) -> Result<&'a str> {
+  string_argument(function, "path", args)
+}
 
-  Ok(render_markdown(source).into())
+/// Gets the `name` argument of a function as a string.
+fn string_argument<'a>(
+  function: &str,
+  name: &str,
+  args: &'a HashMap<String, Value>,
+) -> Result<&'a str> {
+  args.get(name).and_then(Value::as_str).ok_or_else(|| {
+    Error::msg(format!("{} expects a string `{}` argument", function, name))
+  })
 }
@@ -0,0 +1,82 @@
+use std::path::{Component, Path, PathBuf};
+
+/// Returns the root-relative URL for `path`, a path relative to the root of
+/// the site, by putting the path of `base_url` in front of it.
+///
+/// With a base URL of `"https://example.com/docs/v2/"` or `"/docs/v2/"`,
+/// `"/blog/"` becomes `"/docs/v2/blog/"`.
+pub fn relative_url(base_url: &str, path: &str) -> String {
+  let (_, base_path) = split_base_url(base_url);
+  let base_path = base_path.trim_matches('/');
+  let path = path.trim_start_matches('/');
+  if base_path.is_empty() {
+    format!("/{}", path)
+  } else {
+    format!("/{}/{}", base_path, path)
+  }
+}
+
+/// Returns the absolute URL for `path`, a path relative to the root of the
+/// site, by putting `base_url` in front of it.
+///
+/// With a base URL of `"https://example.com/docs/v2/"`, `"/blog/"` becomes
+/// `"https://example.com/docs/v2/blog/"`. If the base URL is only a path, the
+/// result is the same as [`relative_url`].
+pub fn absolute_url(base_url: &str, path: &str) -> String {
+  let (origin, _) = split_base_url(base_url);
+  origin.to_string() + &relative_url(base_url, path)
+}
+
+/// Checks whether a URL is relative to the root of the site, like `/blog/`,
+/// as opposed to relative to the current page or to another site.
+pub fn is_root_relative(url: &str) -> bool {
+  url.starts_with('/') && !url.starts_with("//")
+}
+
+/// Checks whether a URL points to another site or isn't a link to a page at
+/// all, like `mailto:` links.
+pub fn is_external(url: &str) -> bool {
+  if url.starts_with("//") {
+    return true;
+  }
+
+  // A scheme comes before any path, query or fragment.
+  let end = url.find(['/', '?', '#']).unwrap_or(url.len());
+  url[..end].contains(':')
+}
+
+/// Resolves the `.` and `..` components of a path without touching the file
+/// system. A `..` that would go above the start of a relative path is kept,
+/// and one right after the root is dropped.
+pub fn normalize(path: &Path) -> PathBuf {
+  let mut normalized = PathBuf::new();
+  for component in path.components() {
+    match component {
+      Component::CurDir => (),
+      Component::ParentDir => match normalized.components().next_back() {
+        Some(Component::Normal(_)) => {
+          normalized.pop();
+        }
+        Some(Component::RootDir) => (),
+        _ => normalized.push(component),
+      },
+      component => normalized.push(component),
+    }
+  }
+
+  normalized
+}
+
+/// Splits a base URL into its scheme and host, and its path. When the base
+/// URL has no scheme, all of it is the path.
+fn split_base_url(base_url: &str) -> (&str, &str) {
+  let host_start = match base_url.find("://") {
+    Some(index) => index + 3,
+    None => return ("", base_url),
+  };
+
+  match base_url[host_start..].find('/') {
+    Some(index) => base_url.split_at(host_start + index),
+    None => (base_url, ""),
+  }
+}
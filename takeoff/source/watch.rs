@@ -0,0 +1,291 @@
+use std::{
+  path::{Path, PathBuf},
+  sync::mpsc::channel,
+  time::Duration,
+};
+
+use notify::{DebouncedEvent, RecursiveMode, Watcher};
+use tracing::{debug, error, info, instrument, trace};
+
+use crate::{error::Result, Launchpad};
+
+/// How long to wait for filesystem events to settle before rebuilding.
+pub const DEBOUNCE_DELAY: Duration = Duration::from_millis(250);
+
+impl Launchpad {
+  /// Returns the directories [`Launchpad::watch`] will monitor for changes.
+  ///
+  /// This is [`Launchpad::source`], the [`Templates::directory`] and the
+  /// directory part of every glob in [`Launchpad::statics`], leaving out any
+  /// directory that's already inside another one.
+  ///
+  /// [`Templates::directory`]: crate::launchpad::Templates::directory
+  pub fn watch_paths(&self) -> Vec<PathBuf> {
+    let mut candidates = vec![
+      self.source.clone(),
+      self.source.join(&self.templates.directory),
+    ];
+
+    for (source, _) in &self.statics {
+      let glob = self.source.join(source);
+      let directory = glob
+        .components()
+        .take_while(|component| {
+          let component = component.as_os_str().to_string_lossy();
+          !component.contains(['*', '?', '[', '{'])
+        })
+        .collect::<PathBuf>();
+      candidates.push(directory);
+    }
+
+    let mut candidates = candidates
+      .into_iter()
+      .filter_map(|path| path.canonicalize().ok())
+      .collect::<Vec<_>>();
+
+    // Sorting makes parent directories come before their children.
+    candidates.sort();
+    candidates.dedup();
+
+    let mut paths: Vec<PathBuf> = vec![];
+    for path in candidates {
+      if !paths.iter().any(|parent| path.starts_with(parent)) {
+        paths.push(path);
+      }
+    }
+
+    paths
+  }
+
+  /// Watches the [`Launchpad::watch_paths`] and re-runs
+  /// [`Launchpad::take_off`] whenever something changes. This function only
+  /// returns if setting up the watcher fails, the site should already have
+  /// been generated before calling it.
+  pub fn watch(&mut self) -> Result<()> {
+    self.watch_with(|_| {})
+  }
+
+  /// The same as [`Launchpad::watch`] but calls `on_rebuild` after every
+  /// successful rebuild.
+  ///
+  /// Events are debounced with [`DEBOUNCE_DELAY`] and when any of the changed
+  /// files are templates, the [`tera`] instance is reloaded first. Errors
+  /// that happen while rebuilding are logged and then watching continues.
+  #[instrument(skip(self, on_rebuild))]
+  pub fn watch_with<F>(&mut self, mut on_rebuild: F) -> Result<()>
+  where
+    F: FnMut(&Launchpad),
+  {
+    let (sender, receiver) = channel();
+    let mut watcher = notify::watcher(sender, DEBOUNCE_DELAY)?;
+
+    for path in self.watch_paths() {
+      debug!("Watching {:?}", path);
+      watcher.watch(&path, RecursiveMode::Recursive)?;
+    }
+
+    loop {
+      // Collect everything that has already arrived so one burst of changes
+      // only causes a single rebuild.
+      let event = match receiver.recv() {
+        Ok(event) => event,
+        // The watcher has been dropped, so nothing will change anymore.
+        Err(_) => return Ok(()),
+      };
+
+      let mut changed = vec![];
+      changed.extend(self.changed_path(event));
+      changed.extend(receiver.try_iter().filter_map(|e| self.changed_path(e)));
+
+      if changed.is_empty() {
+        continue;
+      }
+
+      trace!("Changed: {:?}", changed);
+      if changed.iter().any(|path| self.is_template(path)) {
+        debug!("Reloading templates");
+        if let Err(err) = self.tera.full_reload() {
+          error!("Error reloading templates: {}", err);
+          continue;
+        }
+      }
+
+      info!("Rebuilding");
+      match self.take_off() {
+        Ok(_) => on_rebuild(self),
+        Err(err) => error!("Error rebuilding: {}", err),
+      }
+    }
+  }
+
+  /// Returns the path a [`DebouncedEvent`] is about, unless the event doesn't
+  /// need a rebuild or happened inside [`Launchpad::output`].
+  fn changed_path(&self, event: DebouncedEvent) -> Option<PathBuf> {
+    let path = match event {
+      DebouncedEvent::Create(path)
+      | DebouncedEvent::Write(path)
+      | DebouncedEvent::Remove(path)
+      | DebouncedEvent::Rename(_, path) => path,
+      DebouncedEvent::Rescan => self.source.clone(),
+      DebouncedEvent::Error(err, path) => {
+        error!("Error watching {:?}: {}", path, err);
+        return None;
+      }
+      _ => return None,
+    };
+
+    if self.is_in_output(&path) {
+      None
+    } else {
+      Some(path)
+    }
+  }
+
+  /// Checks whether a path is inside [`Launchpad::output`].
+  fn is_in_output(&self, path: &Path) -> bool {
+    let output = self
+      .output
+      .canonicalize()
+      .unwrap_or_else(|_| self.output.clone());
+    path.starts_with(output)
+  }
+
+  /// Checks whether a path is inside the
+  /// [`Templates::directory`](crate::launchpad::Templates::directory), so the
+  /// templates have to be reloaded.
+  fn is_template(&self, path: &Path) -> bool {
+    let templates = self.source.join(&self.templates.directory);
+    let templates = templates.canonicalize().unwrap_or(templates);
+    path.starts_with(templates)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    env::temp_dir,
+    fs::{create_dir_all, write},
+    process,
+  };
+
+  use super::*;
+
+  /// Creates a site in a temporary directory with the output, templates and
+  /// Sass inside of the source, and images in an `assets` directory next to
+  /// it. Returns the canonical path of that temporary directory.
+  fn site(name: &str) -> PathBuf {
+    let directory =
+      temp_dir().join(format!("takeoff-test-watch-{}-{}", name, process::id()));
+    for path in [
+      "assets/icons",
+      "site/public",
+      "site/static/scss",
+      "site/templates",
+    ] {
+      create_dir_all(directory.join(path)).unwrap();
+    }
+    write(directory.join("site/index.md"), "# Hi").unwrap();
+    write(directory.join("site/templates/base.html"), "").unwrap();
+
+    directory.canonicalize().unwrap()
+  }
+
+  /// Creates a [`Launchpad`] for a [`site`] with `statics`.
+  fn create_launchpad(
+    directory: &Path,
+    statics: Vec<(&str, &str)>,
+  ) -> Launchpad {
+    let source = directory.join("site");
+    Launchpad::prepare()
+      .output(source.join("public").to_str().unwrap())
+      .source(source.to_str().unwrap())
+      .statics(statics)
+      .build()
+      .unwrap()
+  }
+
+  #[test]
+  fn test_watch_paths() {
+    let directory = site("paths");
+
+    // The templates, output and Sass are all inside the source already.
+    let launchpad = create_launchpad(
+      &directory,
+      vec![
+        ("static/scss/*.scss", "css/"),
+        ("static/scss/_*.scss", "css/"),
+      ],
+    );
+    assert_eq!(launchpad.watch_paths(), vec![directory.join("site")]);
+
+    // Only the directory part of a glob is watched, once.
+    let launchpad = create_launchpad(
+      &directory,
+      vec![
+        ("static/scss/*.scss", "css/"),
+        ("../assets/icons/*.svg", "icons/"),
+        ("../assets/**/*.png", "images/"),
+        ("../assets/[a-z]*.png", "images/"),
+      ],
+    );
+    assert_eq!(
+      launchpad.watch_paths(),
+      vec![directory.join("assets"), directory.join("site")]
+    );
+  }
+
+  #[test]
+  fn test_changed_path() {
+    let directory = site("changed");
+    let launchpad = create_launchpad(&directory, vec![]);
+    let source = directory.join("site");
+
+    // Writing the site would cause an endless loop of rebuilds otherwise.
+    let generated = source.join("public/index.html");
+    assert_eq!(
+      launchpad.changed_path(DebouncedEvent::Write(generated.clone())),
+      None
+    );
+    assert_eq!(
+      launchpad.changed_path(DebouncedEvent::Create(source.join("public"))),
+      None
+    );
+    assert_eq!(
+      launchpad.changed_path(DebouncedEvent::Rename(
+        source.join("index.md"),
+        generated
+      )),
+      None
+    );
+
+    let markdown = source.join("index.md");
+    assert_eq!(
+      launchpad.changed_path(DebouncedEvent::Write(markdown.clone())),
+      Some(markdown.clone())
+    );
+    assert_eq!(
+      launchpad.changed_path(DebouncedEvent::Remove(markdown.clone())),
+      Some(markdown)
+    );
+    assert_eq!(
+      launchpad.changed_path(DebouncedEvent::NoticeWrite(source.join("a.md"))),
+      None
+    );
+    assert_eq!(
+      launchpad.changed_path(DebouncedEvent::Rescan),
+      Some(launchpad.source.clone())
+    );
+  }
+
+  #[test]
+  fn test_is_template() {
+    let directory = site("templates");
+    let launchpad = create_launchpad(&directory, vec![]);
+    let source = directory.join("site");
+
+    assert!(launchpad.is_template(&source.join("templates/base.html")));
+    assert!(launchpad.is_template(&source.join("templates/tags/list.html")));
+    assert!(!launchpad.is_template(&source.join("index.md")));
+    assert!(!launchpad.is_template(&source.join("templates.md")));
+  }
+}
@@ -1,8 +1,29 @@
+use std::{
+  collections::BTreeMap,
+  env::temp_dir,
+  fs::{
+    copy, create_dir_all, metadata, read, read_to_string, remove_dir_all, write,
+  },
+  path::{Path, PathBuf},
+  process,
+  time::UNIX_EPOCH,
+};
+
 use anyhow::Result;
-use takeoff::{launchpad::Mode, Launchpad};
+use takeoff::{
+  document::{Date, Document, FrontMatter, Metadata},
+  launchpad::{LaunchpadBuilder, Mode},
+  links::BrokenLinkKind,
+  markdown::Heading,
+  search::SearchField,
+  section::Section,
+  shortcode::render_shortcodes,
+  Error, Launchpad,
+};
 use tracing::Level;
+use walkdir::WalkDir;
 
-fn create_launchpad(mode: Mode, level: Level) -> Result<Launchpad> {
+fn create_launchpad(mode: Mode, level: Level) -> takeoff::Result<Launchpad> {
   tracing_subscriber::fmt().with_max_level(level).init();
 
   let statics = vec![("static/scss/*.scss", "css/")];
@@ -20,11 +41,1139 @@ fn create_launchpad(mode: Mode, level: Level) -> Result<Launchpad> {
 
 #[test]
 fn test_build_takeoff_website() -> Result<()> {
-  create_launchpad(Mode::Production, Level::INFO)?.take_off()
+  create_launchpad(Mode::Production, Level::INFO)?.take_off()?;
+  Ok(())
 }
 
 #[test]
 #[ignore = "Only run this test when developing the Takeoff website."]
 fn test_develop_takeoff_website() -> Result<()> {
-  create_launchpad(Mode::Development, Level::DEBUG)?.take_off()
+  create_launchpad(Mode::Development, Level::DEBUG)?.take_off()?;
+  Ok(())
+}
+
+#[test]
+fn test_launchpad_from_toml() -> Result<()> {
+  let config = r#"
+    compile_sass = true
+    mode = "production"
+    source = "../docs"
+    statics = [["static/scss/*.scss", "css/"]]
+
+    [templates]
+    default = "document.html"
+  "#;
+
+  let launchpad = LaunchpadBuilder::from_toml(config)?
+    .output("../public")
+    .build()?;
+
+  assert!(launchpad.compile_sass);
+  assert_eq!(launchpad.mode, Mode::Production);
+  assert!(launchpad.output.ends_with("public"));
+  assert!(launchpad.source.ends_with("docs"));
+  assert_eq!(launchpad.statics.len(), 1);
+  assert_eq!(launchpad.templates.default, "document.html");
+  assert_eq!(launchpad.templates.directory.to_str(), Some("templates"));
+  Ok(())
+}
+
+#[test]
+fn test_launchpad_from_config_file() -> Result<()> {
+  let directory = Path::new("tests/fixtures/config");
+  let launchpad =
+    LaunchpadBuilder::from_config_file(&directory.join("takeoff.toml"))?
+      .build()?;
+
+  assert!(launchpad.output.ends_with(directory.join("public")));
+  assert!(launchpad.source.ends_with(directory.join("site")));
+  Ok(())
+}
+
+#[test]
+fn test_incremental_build() -> Result<()> {
+  let directory = unique_temp_dir("takeoff-test-incremental")?;
+  let (source, output) = (directory.join("docs"), directory.join("public"));
+  copy_directory(Path::new("../docs"), &source)?;
+  // A page that lists the content of every other page.
+  write(
+    source.join("templates/listing.html"),
+    "{% for doc in documents %}{{ doc.content | safe }}{% endfor %}",
+  )?;
+  write(
+    source.join("listing.md"),
+    "+++\ntemplate = \"listing.html\"\n+++\n",
+  )?;
+
+  let mut launchpad = Launchpad::prepare()
+    .compile_sass(true)
+    .incremental(true)
+    .output(output.to_str().unwrap())
+    .source(source.to_str().unwrap())
+    .statics(vec![("static/scss/*.scss", "css/")])
+    .templates_default("document.html")
+    .build()?;
+
+  launchpad.take_off()?;
+  let page = output.join("get-started/overview/index.html");
+  let other_page = output.join("docs/launchpad/index.html");
+  let listing = output.join("listing.html");
+  let stylesheet = output.join("css/common.css");
+  let modified = |path| metadata(path).and_then(|meta| meta.modified());
+  let (page_modified, other_page_modified, stylesheet_modified) = (
+    modified(&page)?,
+    modified(&other_page)?,
+    modified(&stylesheet)?,
+  );
+
+  launchpad.take_off()?;
+  assert_eq!(modified(&page)?, page_modified);
+  assert_eq!(modified(&stylesheet)?, stylesheet_modified);
+
+  // Every kind of input should rebuild the output that depends on it, and
+  // only that output.
+  let append = |path: &Path, text: &str| -> Result<()> {
+    let contents = read_to_string(path)?;
+    write(path, contents + text)?;
+    Ok(())
+  };
+
+  append(
+    &source.join("get-started/overview/index.md"),
+    "\nEdited page.\n",
+  )?;
+  launchpad.take_off()?;
+  assert!(read_to_string(&page)?.contains("Edited page."));
+  assert!(read_to_string(&listing)?.contains("Edited page."));
+  assert_eq!(modified(&other_page)?, other_page_modified);
+  assert_eq!(modified(&stylesheet)?, stylesheet_modified);
+
+  append(
+    &source.join("templates/base.html"),
+    "<!-- Edited template -->\n",
+  )?;
+  // Like Launchpad::watch does when a template changes.
+  launchpad.tera.full_reload()?;
+  launchpad.take_off()?;
+  assert!(read_to_string(&page)?.contains("<!-- Edited template -->"));
+  assert_eq!(modified(&stylesheet)?, stylesheet_modified);
+
+  let page_modified = modified(&page)?;
+  append(
+    &source.join("static/scss/components/_page-header.scss"),
+    ".edited-partial { color: red; }\n",
+  )?;
+  launchpad.take_off()?;
+  assert!(read_to_string(&stylesheet)?.contains(".edited-partial"));
+  assert_eq!(modified(&page)?, page_modified);
+  Ok(())
+}
+
+#[test]
+fn test_parallel_output_is_deterministic() -> Result<()> {
+  let mut outputs = vec![];
+  for threads in &[1, 4] {
+    let output = temp_dir().join(format!("takeoff-test-threads-{}", threads));
+    Launchpad::prepare()
+      .output(output.to_str().unwrap())
+      .source("../docs")
+      .templates_default("document.html")
+      .threads(*threads)
+      .build()?
+      .take_off()?;
+    outputs.push(read_directory(&output)?);
+  }
+
+  assert!(outputs[0].contains_key(Path::new("docs/markdown/index.html")));
+  assert_eq!(outputs[0], outputs[1]);
+  Ok(())
+}
+
+#[test]
+fn test_duplicate_output_paths() -> Result<()> {
+  let fixtures = [
+    (
+      "pretty-urls",
+      true,
+      "foo/index.md",
+      "foo.md",
+      "foo/index.html",
+    ),
+    ("pretty-url", false, "a/index.md", "a.md", "a/index.html"),
+    ("slugs", false, "first.md", "second.md", "same.html"),
+  ];
+
+  for (fixture, pretty_urls, first, second, output_path) in fixtures {
+    let launchpad = Launchpad::prepare()
+      .pretty_urls(pretty_urls)
+      .source(&format!("tests/fixtures/collisions/{}", fixture))
+      .build()?;
+
+    match launchpad.parse_documents() {
+      Err(Error::DuplicateOutput {
+        first: first_path,
+        output_path: output,
+        second: second_path,
+      }) => {
+        assert_eq!(first_path, launchpad.source.join(first));
+        assert_eq!(second_path, launchpad.source.join(second));
+        assert_eq!(output, Path::new(output_path));
+      }
+      result => panic!("Expected a duplicate output error, got {:?}", result),
+    }
+  }
+
+  Ok(())
+}
+
+#[test]
+fn test_generated_output_collisions() -> Result<()> {
+  let output = temp_dir().join("takeoff-test-generated-collisions");
+  let launchpad = Launchpad::prepare()
+    .output(output.to_str().unwrap())
+    .pretty_urls(true)
+    .source("tests/fixtures/collisions/taxonomy")
+    .taxonomies(vec!["tags"])
+    .build()?;
+
+  let mut collisions = match launchpad.take_off() {
+    Err(Error::Multiple(errors)) => errors
+      .into_iter()
+      .map(|err| match err {
+        Error::GeneratedOutput {
+          document: Some(document),
+          output_path,
+          template,
+        } => (document, output_path, template),
+        err => panic!("Expected a generated output error, got {:?}", err),
+      })
+      .collect::<Vec<_>>(),
+    result => panic!("Expected multiple errors, got {:?}", result),
+  };
+  collisions.sort();
+
+  assert_eq!(
+    collisions,
+    vec![
+      (
+        launchpad.source.join("tags/rust.md"),
+        PathBuf::from("tags/rust/index.html"),
+        "tags/term.html".to_string(),
+      ),
+      (
+        launchpad.source.join("tags.md"),
+        PathBuf::from("tags/index.html"),
+        "tags/list.html".to_string(),
+      ),
+    ]
+  );
+
+  // Pages after the first of a paginated section can't overwrite documents
+  // either.
+  let launchpad = Launchpad::prepare()
+    .output(output.to_str().unwrap())
+    .pretty_urls(true)
+    .source("tests/fixtures/collisions/pagination")
+    .build()?;
+  match launchpad.take_off() {
+    Err(Error::GeneratedOutput {
+      document: Some(document),
+      output_path,
+      ..
+    }) => {
+      assert_eq!(document, launchpad.source.join("blog/page/2.md"));
+      assert_eq!(output_path, Path::new("blog/page/2/index.html"));
+    }
+    result => panic!("Expected a generated output error, got {:?}", result),
+  }
+
+  // Neither can feeds, like a section feed and a taxonomy feed for the same
+  // directory.
+  let launchpad = Launchpad::prepare()
+    .feeds_atom(true)
+    .feeds_sections(true)
+    .feeds_taxonomies(true)
+    .output(output.to_str().unwrap())
+    .source("tests/fixtures/collisions/feeds")
+    .taxonomies(vec!["tags"])
+    .build()?;
+  match launchpad.take_off() {
+    Err(Error::GeneratedOutput {
+      document: None,
+      output_path,
+      template,
+    }) => {
+      assert_eq!(output_path, Path::new("tags/rust/atom.xml"));
+      assert_eq!(template, "atom.xml");
+    }
+    result => panic!("Expected a generated output error, got {:?}", result),
+  }
+
+  Ok(())
+}
+
+#[test]
+fn test_metadata_front_matter() -> Result<()> {
+  let sources = vec![
+    (
+      "<!-- Metadata\nis_draft = false\n-->\n# Hi",
+      FrontMatter::Comment,
+    ),
+    ("+++\nis_draft = false\n+++\n# Hi", FrontMatter::Toml),
+    ("---\nis_draft: false\n---\n# Hi", FrontMatter::Yaml),
+    ("+++\r\nis_draft = false\r\n+++\r\n# Hi", FrontMatter::Toml),
+    ("---\r\nis_draft: false\r\n---\r\n# Hi", FrontMatter::Yaml),
+    ("+++\nis_draft = false\n+++", FrontMatter::Toml),
+  ];
+
+  for (source, expected) in sources {
+    let (metadata, markdown, front_matter) = Metadata::parse(source);
+    assert!(!metadata?.is_draft);
+    assert!(markdown.trim() == "# Hi" || markdown.is_empty());
+    assert_eq!(front_matter, expected);
+  }
+
+  // Delimiters only count when they're a whole line.
+  let sources = vec![
+    "# Hi",
+    "+++x\nis_draft = false\n+++\n# Hi",
+    "+++\nis_draft = false\n+++x\n# Hi",
+    "---\nis_draft: false\n----\n# Hi",
+    "---\nis_draft: false\n---foo\n# Hi",
+  ];
+
+  for source in sources {
+    let (metadata, markdown, front_matter) = Metadata::parse(source);
+    assert!(metadata?.is_draft);
+    assert_eq!(markdown, source);
+    assert_eq!(front_matter, FrontMatter::None);
+  }
+
+  Ok(())
+}
+
+#[test]
+fn test_metadata_errors() -> Result<()> {
+  let sources = vec![
+    ("+++\nis_draft = false\ntitle = \n+++\n# Hi", 3),
+    ("---\nis_draft: false\ntitle: [\n---\n# Hi", 4),
+  ];
+
+  for (source, expected) in sources {
+    match Metadata::parse(source).0 {
+      Err(Error::Metadata { line, .. }) => assert_eq!(line, Some(expected)),
+      other => panic!("Expected a metadata error, got {:?}", other),
+    }
+  }
+
+  let directory = temp_dir().join("takeoff-test-metadata-errors");
+  create_dir_all(&directory)?;
+  let path = directory.join("broken.md");
+  write(&path, "+++\ntitle = \n+++\n# Hi")?;
+
+  match Document::new(path.clone()) {
+    Err(Error::Metadata {
+      line,
+      path: error_path,
+      ..
+    }) => {
+      assert_eq!(line, Some(2));
+      assert_eq!(error_path, Some(path));
+    }
+    other => panic!("Expected a metadata error, got {:?}", other),
+  }
+
+  Ok(())
+}
+
+#[test]
+fn test_metadata_fields() -> Result<()> {
+  let toml = Metadata::from_toml(
+    r#"
+    date = 2021-01-31
+    slug = "first-post"
+    title = "First Post"
+    updated = 2021-02-01T12:00:00+01:00
+    "#,
+  )?;
+  let yaml = Metadata::from_yaml("date: 2021-01-31\nupdated: 2021-02-01")?;
+
+  assert_eq!(toml.title.as_deref(), Some("First Post"));
+  assert_eq!(toml.slug.as_deref(), Some("first-post"));
+  assert_eq!(toml.date, yaml.date);
+  assert_eq!(toml.date, Some(Date::parse("2021-01-31T00:00:00Z")?));
+  assert!(yaml.updated < toml.updated);
+  assert_eq!(
+    serde_json::to_value(toml.updated)?,
+    "2021-02-01T11:00:00+00:00"
+  );
+
+  assert!(Metadata::from_toml(r#"slug = "../escape""#).is_err());
+  assert!(Metadata::from_toml(r#"date = "not a date""#).is_err());
+  Ok(())
+}
+
+#[test]
+fn test_ignored_documents() -> Result<()> {
+  let modes = [
+    (Mode::Development, vec!["draft.md", "index.md"]),
+    (Mode::Production, vec!["index.md"]),
+  ];
+
+  for (mode, expected) in modes {
+    let output = temp_dir().join(format!("takeoff-test-ignore-{:?}", mode));
+    let launchpad = Launchpad::prepare()
+      .mode(mode)
+      .output(output.to_str().unwrap())
+      .source("tests/fixtures/ignore")
+      .build()?;
+
+    let documents = launchpad.parse_documents()?;
+    let file_names = documents
+      .iter()
+      .map(|doc| doc.source_path.file_name().unwrap().to_str().unwrap())
+      .collect::<Vec<_>>();
+    assert_eq!(file_names, expected);
+
+    launchpad.take_off()?;
+    assert!(!output.join("ignored.html").exists());
+    assert!(!read_to_string(output.join("index.html"))?.contains("Ignored"));
+  }
+
+  Ok(())
+}
+
+#[test]
+fn test_pretty_urls() -> Result<()> {
+  let modes = [
+    (
+      false,
+      vec![
+        ("about.md", "/about.html", "about.html"),
+        ("blog/first-post.md", "/blog/first.html", "blog/first.html"),
+        ("blog/index.md", "/blog/", "blog/index.html"),
+        ("blog/plain.md", "/blog/plain.html", "blog/plain.html"),
+        ("index.md", "/", "index.html"),
+      ],
+    ),
+    (
+      true,
+      vec![
+        ("about.md", "/about/", "about/index.html"),
+        (
+          "blog/first-post.md",
+          "/blog/first/",
+          "blog/first/index.html",
+        ),
+        ("blog/index.md", "/blog/", "blog/index.html"),
+        ("blog/plain.md", "/blog/plain.html", "blog/plain.html"),
+        ("index.md", "/", "index.html"),
+      ],
+    ),
+  ];
+
+  for (pretty_urls, expected) in modes {
+    let output =
+      temp_dir().join(format!("takeoff-test-pretty-urls-{}", pretty_urls));
+    let launchpad = Launchpad::prepare()
+      .output(output.to_str().unwrap())
+      .pretty_urls(pretty_urls)
+      .source("tests/fixtures/urls")
+      .build()?;
+
+    let documents = launchpad.parse_documents()?;
+    assert_eq!(documents.len(), expected.len());
+    launchpad.take_off()?;
+
+    for (source, permalink, destination) in expected {
+      let document = documents
+        .iter()
+        .find(|doc| doc.source_path == launchpad.source.join(source))
+        .unwrap();
+      assert_eq!(document.permalink, permalink);
+      assert!(output.join(destination).is_file());
+    }
+
+    let index = read_to_string(output.join("index.html"))?;
+    assert!(index.contains(r#"<a href="/blog/plain.html">Plain</a>"#));
+  }
+
+  Ok(())
+}
+
+#[test]
+fn test_document_urls() -> Result<()> {
+  let base_urls = [
+    ("/", "/blog/first/"),
+    ("/docs/v2", "/docs/v2/blog/first/"),
+    ("https://example.com/docs/v2/", "/docs/v2/blog/first/"),
+    ("https://example.com", "/blog/first/"),
+  ];
+
+  for (base_url, expected) in base_urls {
+    let launchpad = Launchpad::prepare()
+      .base_url(base_url)
+      .pretty_urls(true)
+      .source("tests/fixtures/urls")
+      .build()?;
+
+    let documents = launchpad.parse_documents()?;
+    let document = documents
+      .iter()
+      .find(|doc| doc.permalink == "/blog/first/")
+      .unwrap();
+    assert_eq!(document.url, expected);
+    assert_eq!(document.output_path, Path::new("blog/first/index.html"));
+
+    let json = serde_json::to_string(document)?;
+    assert!(!json.contains("source_path"));
+  }
+
+  Ok(())
+}
+
+#[test]
+fn test_base_url() -> Result<()> {
+  let output = temp_dir().join("takeoff-test-base-url");
+  let launchpad = Launchpad::prepare()
+    .base_url("https://example.com/docs/v2/")
+    .output(output.to_str().unwrap())
+    .pretty_urls(true)
+    .source("tests/fixtures/urls")
+    .build()?;
+
+  assert_eq!(launchpad.url("/blog/"), "/docs/v2/blog/");
+  assert_eq!(
+    launchpad.absolute_url("/blog/"),
+    "https://example.com/docs/v2/blog/"
+  );
+
+  launchpad.take_off()?;
+  let about = read_to_string(output.join("about/index.html"))?;
+  let expected = [
+    r#"<link rel="canonical" href="https://example.com/docs/v2/about/">"#,
+    r#"<a href="/docs/v2/">Home</a>"#,
+    r#"<p><a href="/docs/v2/blog/">Blog</a></p>"#,
+    r#"<a href="/docs/v2/blog/first/">First Post</a>"#,
+    r#"<a href="/docs/v2/blog/">blog</a>"#,
+    r#"<a href="https://example.org/">elsewhere</a>"#,
+    r#"<img src="/docs/v2/logo.png" alt="Logo" />"#,
+  ];
+
+  for html in expected {
+    assert!(about.contains(html), "{:?} not found in {}", html, about);
+  }
+
+  Ok(())
+}
+
+#[test]
+fn test_sections() -> Result<()> {
+  let output = temp_dir().join("takeoff-test-sections");
+  let launchpad = Launchpad::prepare()
+    .output(output.to_str().unwrap())
+    .source("tests/fixtures/sections")
+    .build()?;
+
+  let documents = launchpad.parse_documents()?;
+  let sections = Section::from_documents(&documents);
+  let paths = sections.keys().map(|path| path.to_str().unwrap());
+  assert_eq!(
+    paths.collect::<Vec<_>>(),
+    vec!["", "blog", "docs", "docs/guide"]
+  );
+
+  let title = |position: usize| documents[position].metadata.title.as_deref();
+  let guide = &sections[Path::new("docs/guide")];
+  assert_eq!(guide.ancestors, vec![Path::new(""), Path::new("docs")]);
+  assert_eq!(title(guide.index.unwrap()), Some("Guide"));
+  assert_eq!(
+    guide
+      .pages
+      .iter()
+      .map(|page| title(*page))
+      .collect::<Vec<_>>(),
+    vec![Some("Install")]
+  );
+
+  let blog = &sections[Path::new("blog")];
+  assert_eq!(blog.index, None);
+  assert_eq!(blog.permalink(&documents), "/blog/");
+  assert_eq!(
+    sections[Path::new("")].subsections,
+    vec![Path::new("blog"), Path::new("docs")]
+  );
+
+  launchpad.take_off()?;
+  let install = read_to_string(output.join("docs/guide/install.html"))?;
+  assert!(install.contains(r#"<a href="/">Home</a> /"#));
+  assert!(install.contains(r#"<a href="/docs/">Docs</a> /"#));
+  assert!(install.contains(r#"<a href="/docs/guide/">Guide</a>"#));
+
+  let docs = read_to_string(output.join("docs/index.html"))?;
+  assert!(docs.contains("<li>FAQ</li>"));
+  assert!(docs.contains("<li>Guide (1)</li>"));
+
+  let home = read_to_string(output.join("index.html"))?;
+  assert!(home.contains("<li>Untitled (1)</li>"));
+  assert!(home.contains("<li>Docs (1)</li>"));
+
+  Ok(())
+}
+
+#[test]
+fn test_sorting() -> Result<()> {
+  let output = temp_dir().join("takeoff-test-sorting");
+  let launchpad = Launchpad::prepare()
+    .output(output.to_str().unwrap())
+    .source("tests/fixtures/sorting")
+    .build()?;
+  launchpad.take_off()?;
+
+  let expected = [
+    ("index.html", "Beta, Alpha, Gamma", "", ""),
+    ("alpha.html", "Beta, Alpha, Gamma", "Beta", "Gamma"),
+    ("gamma.html", "Beta, Alpha, Gamma", "Alpha", ""),
+    ("blog/index.html", "Third, Second, First, Undated", "", ""),
+    (
+      "blog/third.html",
+      "Third, Second, First, Undated",
+      "",
+      "Second",
+    ),
+    (
+      "blog/undated.html",
+      "Third, Second, First, Undated",
+      "First",
+      "",
+    ),
+  ];
+
+  for (file, pages, previous, next) in expected {
+    let html = read_to_string(output.join(file))?;
+    let expected = format!("{}\nprevious: {}\nnext: {}", pages, previous, next);
+    assert_eq!(html.trim(), expected.trim(), "{}", file);
+  }
+
+  Ok(())
+}
+
+#[test]
+fn test_taxonomies() -> Result<()> {
+  let output = temp_dir().join("takeoff-test-taxonomies");
+  let launchpad = Launchpad::prepare()
+    .output(output.to_str().unwrap())
+    .source("tests/fixtures/taxonomies")
+    .taxonomies(vec!["tags"])
+    .build()?;
+
+  let documents = launchpad.parse_documents()?;
+  let terms = launchpad.taxonomies[0].terms(&documents);
+  let slugs = terms.iter().map(|term| term.slug.as_str());
+  assert_eq!(slugs.collect::<Vec<_>>(), vec!["rust", "static-sites"]);
+  assert_eq!(terms[0].name, "Rust");
+
+  launchpad.take_off()?;
+  let list = read_to_string(output.join("tags/index.html"))?;
+  assert!(list.contains("<h1>tags</h1>"));
+  assert!(list.contains(r#"<a href="/tags/rust/">Rust (2)</a>"#));
+  assert!(
+    list.contains(r#"<a href="/tags/static-sites/">Static Sites (1)</a>"#)
+  );
+
+  let rust = read_to_string(output.join("tags/rust/index.html"))?;
+  assert!(rust.contains(r#"<h1><a href="/tags/">tags</a>: Rust</h1>"#));
+  assert!(rust.contains("Two, One"));
+
+  let one = read_to_string(output.join("one.html"))?;
+  assert!(one.contains(r#"<a href="/tags/rust/">Rust</a>"#));
+  assert!(one.contains(r#"<a href="/tags/static-sites/">Static Sites</a>"#));
+  assert!(one.contains(r#"<a href="/tags/">All tags</a>"#));
+
+  for name in ["Blog Tags", "../tags", ""] {
+    match Launchpad::prepare().taxonomies(vec![name]).build() {
+      Err(Error::Taxonomy(_)) => {}
+      result => panic!("Expected a taxonomy error, got {:?}", result),
+    }
+  }
+
+  Ok(())
+}
+
+#[test]
+fn test_feeds() -> Result<()> {
+  let output = temp_dir().join("takeoff-test-feeds");
+  let launchpad = Launchpad::prepare()
+    .base_url("https://example.com/")
+    .feeds_atom(true)
+    .feeds_limit(Some(2))
+    .feeds_rss(true)
+    .feeds_sections(true)
+    .feeds_taxonomies(true)
+    .feeds_title("My Site")
+    .output(output.to_str().unwrap())
+    .source("tests/fixtures/feeds")
+    .taxonomies(vec!["tags"])
+    .build()?;
+  launchpad.take_off()?;
+
+  let atom = read_to_string(output.join("atom.xml"))?;
+  assert!(atom.contains("<title>My Site</title>"));
+  assert!(atom.contains("<updated>2021-03-01T00:00:00+00:00</updated>"));
+  assert!(atom.contains("<title>Tom &amp; Jerry</title>"));
+  assert!(atom.contains("<summary>The first post.</summary>"));
+  assert!(atom.contains("Some &lt;em&gt;emphasis&lt;"));
+  assert!(atom.find("Second").unwrap() < atom.find("Tom").unwrap());
+  assert!(!atom.contains("About"));
+
+  // The RSS template is overridden by the one in the fixture.
+  let rss = read_to_string(output.join("rss.xml"))?;
+  assert_eq!(rss.trim(), "My Site: Second, Tom &amp; Jerry");
+  let blog = read_to_string(output.join("blog/rss.xml"))?;
+  assert_eq!(blog.trim(), "Blog: Second, Tom &amp; Jerry");
+  let rust = read_to_string(output.join("tags/rust/rss.xml"))?;
+  assert_eq!(rust.trim(), "rust: Tom &amp; Jerry");
+
+  let atom = read_to_string(output.join("blog/atom.xml"))?;
+  assert!(atom.contains(r#"<link href="https://example.com/blog/"/>"#));
+  assert!(!atom.contains("&#x2F;"));
+
+  // Feeds without any dated entries were updated when their documents were
+  // last modified, so they're the same in every build.
+  let atom = read_to_string(output.join("notes/atom.xml"))?;
+  let updated = atom.split("<updated>").nth(1).unwrap();
+  let updated = Date::parse(&updated[..updated.find('<').unwrap()])?;
+  let modified = metadata("tests/fixtures/feeds/notes/idea.md")?.modified()?;
+  let modified = modified.duration_since(UNIX_EPOCH).unwrap();
+  assert_eq!(updated.0.timestamp(), modified.as_secs() as i64);
+  assert!(!atom.contains("<entry>"));
+
+  launchpad.take_off()?;
+  assert_eq!(read_to_string(output.join("notes/atom.xml"))?, atom);
+
+  Ok(())
+}
+
+#[test]
+fn test_sitemap() -> Result<()> {
+  // Drafts are rendered in development mode but never listed in the sitemap.
+  for mode in [Mode::Development, Mode::Production] {
+    let output = temp_dir().join(format!("takeoff-test-sitemap-{:?}", mode));
+    let launchpad = Launchpad::prepare()
+      .base_url("https://example.com/")
+      .mode(mode)
+      .output(output.to_str().unwrap())
+      .robots_txt(true)
+      .sitemap(true)
+      .source("tests/fixtures/sitemap")
+      .build()?;
+    launchpad.take_off()?;
+
+    let sitemap = read_to_string(output.join("sitemap.xml"))?;
+    assert!(sitemap.contains("<loc>https://example.com/</loc>"));
+    assert!(sitemap.contains("<loc>https://example.com/post.html</loc>"));
+    assert!(sitemap.contains("<lastmod>2021-02-01T12:00:00+00:00</lastmod>"));
+    // Documents without a date fall back to the modification time.
+    assert_eq!(sitemap.matches("<lastmod>").count(), 2);
+    assert!(!sitemap.contains("draft"));
+
+    let robots = read_to_string(output.join("robots.txt"))?;
+    assert!(robots.contains("Sitemap: https://example.com/sitemap.xml"));
+  }
+
+  Ok(())
+}
+
+#[test]
+fn test_search_index() -> Result<()> {
+  let output = temp_dir().join("takeoff-test-search-index");
+  let launchpad = Launchpad::prepare()
+    .output(output.to_str().unwrap())
+    .search_index(true)
+    .search_index_max_text_length(14)
+    .source("tests/fixtures/search")
+    .build()?;
+  launchpad.take_off()?;
+
+  let index = read_to_string(output.join("search_index.json"))?;
+  let index: serde_json::Value = serde_json::from_str(&index)?;
+  assert_eq!(
+    index[0],
+    serde_json::json!({
+      "headings": ["Getting started", "Configuration"],
+      "tags": ["docs"],
+      "text": "Getting",
+      "title": "Guide",
+      "url": "/guide.html",
+    })
+  );
+  assert_eq!(index[1]["text"], "Lorem ipsum");
+
+  // The draft is rendered in development mode, but isn't searchable.
+  assert_eq!(index.as_array().unwrap().len(), 2);
+  assert!(output.join("wip.html").is_file());
+
+  let documents = launchpad.parse_documents()?;
+  assert_eq!(
+    documents[0].text,
+    "Getting started Install it with cargo install, then run it. \
+     Configuration fn main() {}"
+  );
+
+  let launchpad = Launchpad::prepare()
+    .output(output.to_str().unwrap())
+    .search_index(true)
+    .search_index_fields(vec![SearchField::Title, SearchField::Url])
+    .source("tests/fixtures/search")
+    .build()?;
+  launchpad.take_off()?;
+
+  let index = read_to_string(output.join("search_index.json"))?;
+  assert_eq!(
+    index,
+    r#"[{"title":"Guide","url":"/guide.html"},{"title":"Long","url":"/long.html"}]"#
+  );
+
+  Ok(())
+}
+
+#[test]
+fn test_table_of_contents() -> Result<()> {
+  let output = temp_dir().join("takeoff-test-table-of-contents");
+  let launchpad = Launchpad::prepare()
+    .heading_anchors(true)
+    .output(output.to_str().unwrap())
+    .source("tests/fixtures/toc")
+    .build()?;
+
+  let documents = launchpad.parse_documents()?;
+  let toc = &documents[0].toc;
+  let ids = |headings: &[Heading]| {
+    headings
+      .iter()
+      .map(|heading| heading.id.clone())
+      .collect::<Vec<_>>()
+  };
+  assert_eq!(ids(toc), vec!["usage", "usage-2"]);
+  assert_eq!(ids(&toc[0].children), vec!["install-takeoff", "usage-1"]);
+  assert_eq!(ids(&toc[0].children[0].children), vec!["from-source"]);
+  assert_eq!(toc[0].children[0].title, "Install takeoff");
+
+  launchpad.take_off()?;
+  let html = read_to_string(output.join("index.html"))?;
+  assert!(html.contains(
+    "<li><a href=\"#install-takeoff\">Install takeoff</a><ul>\n\
+     <li><a href=\"#from-source\">From source</a></li>"
+  ));
+  assert!(html.contains(
+    "<h2 id=\"install-takeoff\">Install <code>takeoff</code>\
+     <a class=\"heading-anchor\" href=\"#install-takeoff\">#</a></h2>"
+  ));
+  assert!(html.contains("<h1 id=\"usage-2\">Usage"));
+
+  Ok(())
+}
+
+#[test]
+fn test_admonitions() -> Result<()> {
+  let output = temp_dir().join("takeoff-test-admonitions");
+  let launchpad = Launchpad::prepare()
+    .admonitions(true)
+    .admonitions_class("callout")
+    .admonitions_titles(vec![
+      ("NOTE", "Note"),
+      ("tip", "Tip"),
+      ("warning", "Warning"),
+    ])
+    .output(output.to_str().unwrap())
+    .source("tests/fixtures/admonitions")
+    .build()?;
+  launchpad.take_off()?;
+
+  let index = read_to_string(output.join("index.html"))?;
+  let expected = [
+    "<aside class=\"callout note\">\n<p class=\"callout-title\">Note</p>\n\
+     <p>Takeoff renders <strong>Markdown</strong> inside admonitions.</p>\n\
+     <ul>\n<li>Even lists.</li>\n</ul>\n</aside>",
+    "<aside class=\"callout tip\">\n<p class=\"callout-title\">Pro tip</p>\n\
+     <p>Titles can be changed.</p>\n</aside>",
+    "<aside class=\"callout warning\">\n\
+     <p class=\"callout-title\">Careful</p>\n</aside>",
+    "<blockquote>\n<p>[!UNKNOWN]\nUnknown kinds stay blockquotes.</p>\n\
+     </blockquote>",
+    "<blockquote>\n<p>Regular blockquotes stay too.</p>\n</blockquote>",
+  ];
+  for html in &expected {
+    assert!(index.contains(html), "{:?} not in {:?}", html, index);
+  }
+
+  let launchpad = Launchpad::prepare()
+    .output(output.to_str().unwrap())
+    .source("tests/fixtures/admonitions")
+    .build()?;
+  launchpad.take_off()?;
+  let index = read_to_string(output.join("index.html"))?;
+  assert!(!index.contains("<aside"));
+  assert!(index.contains("<p>[!NOTE]\nTakeoff renders"));
+
+  Ok(())
+}
+
+#[test]
+fn test_check_links() -> Result<()> {
+  let output = temp_dir().join("takeoff-test-check-links");
+  let launchpad = Launchpad::prepare()
+    .output(output.to_str().unwrap())
+    .source("tests/fixtures/links")
+    .build()?;
+  let documents = launchpad.take_off()?;
+
+  let mut broken = launchpad
+    .check_links(&documents)?
+    .into_iter()
+    .map(|link| {
+      let file = link.path.file_name().unwrap().to_string_lossy().to_string();
+      (link.kind, file, link.line, link.url)
+    })
+    .collect::<Vec<_>>();
+  broken.sort_by(|a, b| (&a.1, a.2).cmp(&(&b.1, b.2)));
+  assert_eq!(
+    broken,
+    vec![
+      (
+        BrokenLinkKind::MissingFile,
+        "about.html".to_string(),
+        1,
+        "/css/style.css".to_string()
+      ),
+      (
+        BrokenLinkKind::MissingFile,
+        "index.html".to_string(),
+        1,
+        "/css/style.css".to_string()
+      ),
+      (
+        BrokenLinkKind::MissingPage,
+        "index.md".to_string(),
+        8,
+        "/missing/".to_string()
+      ),
+      (
+        BrokenLinkKind::MissingAnchor,
+        "index.md".to_string(),
+        9,
+        "/about.html#nope".to_string()
+      ),
+    ]
+  );
+
+  let launchpad = Launchpad::prepare()
+    .deny_broken_links(true)
+    .mode_production()
+    .output(output.to_str().unwrap())
+    .source("tests/fixtures/links")
+    .build()?;
+  assert!(matches!(
+    launchpad.take_off(),
+    Err(Error::BrokenLinks(links)) if links.len() == 4
+  ));
+
+  Ok(())
+}
+
+#[test]
+fn test_markdown_links() -> Result<()> {
+  let output = temp_dir().join("takeoff-test-markdown-links");
+  let launchpad = Launchpad::prepare()
+    .base_url("https://example.com/docs/")
+    .output(output.to_str().unwrap())
+    .pretty_urls(true)
+    .source("tests/fixtures/markdown-links")
+    .build()?;
+  launchpad.take_off()?;
+
+  let index = read_to_string(output.join("index.html"))?;
+  assert!(index.contains(r#"<a href="/docs/installation/">installation</a>"#));
+  assert!(index.contains(
+    r#"<a href="/docs/blog/hello-world/#getting-started">first post</a>"#
+  ));
+  assert!(index.contains(r#"<a href="/docs/blog/hello-world/?ref=home">"#));
+  assert!(index.contains(r#"<a href="README.txt">README</a>"#));
+
+  let installation = read_to_string(output.join("installation/index.html"))?;
+  assert!(installation.contains(r#"<a href="/docs/">back home</a>"#));
+
+  let post = read_to_string(output.join("blog/hello-world/index.html"))?;
+  assert!(post.contains(r#"<a href="/docs/installation/#requirements">"#));
+
+  let launchpad = Launchpad::prepare()
+    .output(output.to_str().unwrap())
+    .source("tests/fixtures/markdown-links-missing")
+    .build()?;
+  match launchpad.take_off() {
+    Err(Error::MissingDocument { line, path, url }) => {
+      assert_eq!(line, 5);
+      assert!(path.ends_with("markdown-links-missing/index.md"));
+      assert_eq!(url, "missing.md");
+    }
+    result => panic!("Expected a missing document, got {:?}", result),
+  }
+
+  Ok(())
+}
+
+#[test]
+fn test_shortcodes() -> Result<()> {
+  let output = temp_dir().join("takeoff-test-shortcodes");
+  let launchpad = Launchpad::prepare()
+    .base_url("/blog/")
+    .output(output.to_str().unwrap())
+    .source("tests/fixtures/shortcodes")
+    .build()?;
+  launchpad.take_off()?;
+
+  let index = read_to_string(output.join("index.html"))?;
+  assert!(index.contains(
+    r#"<figure><img src="&#x2F;blog&#x2F;cat.png" alt="A &quot;cat&quot;" width="200"></figure>"#
+  ));
+  assert!(index.contains(
+    "<aside class=\"note tip small\"><p>Heads up</p><p>Some <strong>Markdown</strong> in a note.</p>"
+  ));
+  assert!(
+    index.contains(r#"<code>{{ figure(src=&quot;/cat.png&quot;) }}</code>"#)
+  );
+  assert!(index.contains(r#"{{ figure(src=&quot;/dog.png&quot;) }}"#));
+
+  let error = |source: &str| match render_shortcodes(source, &launchpad.tera) {
+    Err(Error::Shortcode { line, message, .. }) => (line, message),
+    result => panic!("Expected a shortcode error, got {:?}", result),
+  };
+  assert_eq!(
+    error("Text\n\n{{ figure(src=\"/cat.png\" alt=\"\") }}"),
+    (3, "Expected `,` or `)` after argument `src`".to_string())
+  );
+  assert_eq!(
+    error("{% note(title=\"Oops\") %}\nNever closed."),
+    (1, "Missing `{% end %}` for shortcode `note`".to_string())
+  );
+  assert!(matches!(
+    render_shortcodes("{{ missing() }}", &launchpad.tera),
+    Err(Error::Template { name: Some(name), .. })
+      if name == "shortcodes/missing.html"
+  ));
+  assert_eq!(
+    render_shortcodes("{{ not a shortcode }} {% if x %}", &launchpad.tera)?,
+    "{{ not a shortcode }} {% if x %}"
+  );
+
+  // Shortcodes in any kind of code block are left alone.
+  for code in [
+    "Text\n\n    {{ figure(src=\"/cat.png\") }}\n",
+    "> ```\n> {{ figure(src=\"/cat.png\") }}\n> ```\n",
+    "- ```\n  {{ figure(src=\"/cat.png\") }}\n  ```\n",
+    "```\n```rust\n{{ figure(src=\"/cat.png\") }}\n```\n",
+  ] {
+    assert_eq!(render_shortcodes(code, &launchpad.tera)?, code);
+  }
+
+  Ok(())
+}
+
+#[test]
+fn test_pagination() -> Result<()> {
+  let output = temp_dir().join("takeoff-test-pagination");
+  let config = format!(
+    r#"
+    output = {:?}
+    source = "tests/fixtures/pagination"
+
+    [[taxonomies]]
+    name = "tags"
+    paginate_by = 3
+    "#,
+    output
+  );
+
+  let launchpad = LaunchpadBuilder::from_toml(&config)?.build()?;
+  launchpad.take_off()?;
+
+  let expected = [
+    (
+      "blog/index.html",
+      "1/3: Post 5, Post 4",
+      "",
+      "/blog/page/2/",
+    ),
+    (
+      "blog/page/2/index.html",
+      "2/3: Post 3, Post 2",
+      "/blog/",
+      "/blog/page/3/",
+    ),
+    ("blog/page/3/index.html", "3/3: Post 1", "/blog/page/2/", ""),
+    (
+      "tags/all/index.html",
+      "1/2: Post 5, Post 4, Post 3",
+      "",
+      "/tags/all/page/2/",
+    ),
+    (
+      "tags/all/page/2/index.html",
+      "2/2: Post 2, Post 1",
+      "/tags/all/",
+      "",
+    ),
+  ];
+
+  for (file, pages, previous, next) in expected {
+    let html = read_to_string(output.join(file))?;
+    let expected = format!("{}\nprevious: {}\nnext: {}", pages, previous, next);
+    assert_eq!(html.trim(), expected.trim(), "{}", file);
+  }
+
+  let post = read_to_string(output.join("blog/post-1.html"))?;
+  assert_eq!(post.trim(), "Post 1");
+
+  Ok(())
+}
+
+/// Creates an empty directory in the temporary directory that no other test
+/// run uses, so leftovers from previous runs can't affect the test.
+fn unique_temp_dir(name: &str) -> Result<PathBuf> {
+  let directory = temp_dir().join(format!("{}-{}", name, process::id()));
+  if directory.exists() {
+    remove_dir_all(&directory)?;
+  }
+
+  create_dir_all(&directory)?;
+  Ok(directory)
+}
+
+/// Recursively copies the files in `source` to `destination`.
+fn copy_directory(source: &Path, destination: &Path) -> Result<()> {
+  for entry in WalkDir::new(source) {
+    let entry = entry?;
+    let target = destination.join(entry.path().strip_prefix(source)?);
+    if entry.file_type().is_dir() {
+      create_dir_all(target)?;
+    } else {
+      copy(entry.path(), target)?;
+    }
+  }
+
+  Ok(())
+}
+
+/// Reads every file in `directory`, keyed by its path relative to it.
+fn read_directory(directory: &Path) -> Result<BTreeMap<PathBuf, Vec<u8>>> {
+  let mut files = BTreeMap::new();
+  for entry in WalkDir::new(directory) {
+    let entry = entry?;
+    if entry.file_type().is_file() {
+      let path = entry.path().strip_prefix(directory)?.to_path_buf();
+      files.insert(path, read(entry.path())?);
+    }
+  }
+
+  Ok(files)
 }
@@ -1,6 +1,14 @@
+use std::path::Path;
+
 use anyhow::Result;
-use clap::{crate_authors, crate_description, crate_version, App, Arg};
-use takeoff::{launchpad::Mode, Launchpad};
+use clap::{
+  crate_authors, crate_description, crate_version, App, Arg, ArgMatches,
+  SubCommand,
+};
+use takeoff::{
+  launchpad::{LaunchpadBuilder, Mode, CONFIG_FILE_NAME},
+  Launchpad,
+};
 use tracing_subscriber::filter::LevelFilter;
 
 pub fn main() -> Result<()> {
@@ -10,6 +18,7 @@ pub fn main() -> Result<()> {
     .version(crate_version!())
     .arg(
       Arg::with_name("verbosity")
+        .global(true)
         .help("The maximum tracing level to output.")
         .short("l")
         .long("verbosity")
@@ -19,15 +28,84 @@ pub fn main() -> Result<()> {
           "silent", "error", "warn", "info", "debug", "trace",
         ]),
     )
+    .arg(boolean(
+      Arg::with_name("admonitions")
+        .global(true)
+        .help(
+          "Whether to render blockquotes starting with a marker like [!NOTE] \
+as admonitions.",
+        )
+        .long("admonitions"),
+    ))
     .arg(
+      Arg::with_name("base url")
+        .global(true)
+        .help(
+          "The URL the site will be deployed at, or only its path when \
+deploying under a sub-path.",
+        )
+        .short("b")
+        .long("base-url")
+        .takes_value(true)
+        .default_value("/"),
+    )
+    .arg(
+      Arg::with_name("config")
+        .global(true)
+        .help(
+          "The configuration file to read, if it exists. Any other options \
+passed on the command-line override the values from this file. The source and \
+output in it are relative to the directory the file is in.",
+        )
+        .long("config")
+        .takes_value(true)
+        .default_value(CONFIG_FILE_NAME),
+    )
+    .arg(boolean(
       Arg::with_name("compile sass")
+        .global(true)
         .help("Whether to compile .scss files found in the statics.")
         .short("c")
-        .long("compile-sass")
-        .takes_value(false),
+        .long("compile-sass"),
+    ))
+    .arg(boolean(
+      Arg::with_name("deny broken links")
+        .global(true)
+        .help(
+          "Fail the build when the site has broken links. Only applies in \
+production mode.",
+        )
+        .long("deny-broken-links"),
+    ))
+    .arg(
+      Arg::with_name("feeds")
+        .global(true)
+        .help("The feed formats to generate for the site.")
+        .short("e")
+        .long("feeds")
+        .takes_value(true)
+        .multiple(true)
+        .possible_values(&["atom", "rss"]),
     )
+    .arg(boolean(
+      Arg::with_name("heading anchors")
+        .global(true)
+        .help("Whether to add a link to itself to every heading.")
+        .long("heading-anchors"),
+    ))
+    .arg(boolean(
+      Arg::with_name("incremental")
+        .global(true)
+        .help(
+          "Only regenerate the outputs whose sources have changed since the \
+previous build. Only applies in development mode.",
+        )
+        .short("i")
+        .long("incremental"),
+    ))
     .arg(
       Arg::with_name("mode")
+        .global(true)
         .help(
           "Whether to run in development or production mode. Development \
 outputs all documents while production skips documents with is_draft = true.",
@@ -40,14 +118,44 @@ outputs all documents while production skips documents with is_draft = true.",
     )
     .arg(
       Arg::with_name("output")
+        .global(true)
         .help("The directory to output the resulting files.")
         .short("o")
         .long("output")
         .takes_value(true)
         .default_value("public"),
     )
+    .arg(boolean(
+      Arg::with_name("pretty urls")
+        .global(true)
+        .help(
+          "Output documents as index.html inside a directory with their \
+name, so they can be linked to without the .html extension.",
+        )
+        .short("p")
+        .long("pretty-urls"),
+    ))
+    .arg(boolean(
+      Arg::with_name("robots txt")
+        .global(true)
+        .help("Whether to generate a robots.txt.")
+        .long("robots-txt"),
+    ))
+    .arg(boolean(
+      Arg::with_name("search index")
+        .global(true)
+        .help("Whether to generate a JSON search index of every document.")
+        .long("search-index"),
+    ))
+    .arg(boolean(
+      Arg::with_name("sitemap")
+        .global(true)
+        .help("Whether to generate a sitemap.xml listing every document.")
+        .long("sitemap"),
+    ))
     .arg(
       Arg::with_name("source")
+        .global(true)
         .help("The directory to look for source files.")
         .short("s")
         .long("source")
@@ -56,6 +164,7 @@ outputs all documents while production skips documents with is_draft = true.",
     )
     .arg(
       Arg::with_name("statics")
+        .global(true)
         .help(
           "Static files to include in processing, relative to the source \
 directory. Input must be a valid glob and a directory, separated by a colon.",
@@ -65,8 +174,18 @@ directory. Input must be a valid glob and a directory, separated by a colon.",
         .takes_value(true)
         .multiple(true),
     )
+    .arg(
+      Arg::with_name("taxonomies")
+        .global(true)
+        .help("The taxonomies to group documents by, like tags or categories.")
+        .short("x")
+        .long("taxonomies")
+        .takes_value(true)
+        .multiple(true),
+    )
     .arg(
       Arg::with_name("templates default")
+        .global(true)
         .help("The template to use when a document doesn't specify one.")
         .short("t")
         .long("templates-default")
@@ -75,6 +194,7 @@ directory. Input must be a valid glob and a directory, separated by a colon.",
     )
     .arg(
       Arg::with_name("templates directory")
+        .global(true)
         .help(
           "The directory to look for templates in, relative to the \
 source directory.",
@@ -84,6 +204,48 @@ source directory.",
         .takes_value(true)
         .default_value("templates"),
     )
+    .arg(
+      Arg::with_name("threads")
+        .global(true)
+        .help(
+          "The number of threads to render documents with, 0 uses one \
+thread per CPU.",
+        )
+        .short("j")
+        .long("threads")
+        .takes_value(true)
+        .default_value("0"),
+    )
+    .arg(
+      Arg::with_name("watch")
+        .global(true)
+        .help(
+          "Keep running and rebuild whenever source files, templates or \
+statics change.",
+        )
+        .short("w")
+        .long("watch")
+        .takes_value(false),
+    )
+    .subcommand(SubCommand::with_name("check").about(
+      "Build the site and check it for broken links, exiting with an error \
+when any are found.",
+    ))
+    .subcommand(
+      SubCommand::with_name("serve")
+        .about(
+          "Build the site, serve it locally and rebuild when files change. \
+In development mode pages are reloaded automatically after every rebuild.",
+        )
+        .arg(
+          Arg::with_name("address")
+            .help("The address to serve the site on.")
+            .short("a")
+            .long("address")
+            .takes_value(true)
+            .default_value("127.0.0.1:8080"),
+        ),
+    )
     .get_matches();
 
   let verbosity = match cli.value_of("verbosity").unwrap() {
@@ -98,16 +260,87 @@ source directory.",
 
   tracing_subscriber::fmt().with_max_level(verbosity).init();
 
-  let compile_sass = cli.is_present("compile sass");
-  let mode = match cli.value_of("mode").unwrap() {
-    "development" => Mode::Development,
-    "production" => Mode::Production,
-    _ => unreachable!(),
+  let config = Path::new(cli.value_of("config").unwrap());
+  let mut launchpad = if config.exists() {
+    LaunchpadBuilder::from_config_file(config)?
+  } else if cli.occurrences_of("config") > 0 {
+    eprintln!("Configuration file {:?} does not exist", config);
+    std::process::exit(1);
+  } else {
+    Launchpad::prepare()
   };
-  let output = cli.value_of("output").unwrap();
-  let source = cli.value_of("source").unwrap();
-  let statics = {
-    let statics = cli.values_of("statics").unwrap_or_default();
+
+  // Only override the configuration file with options that were explicitly
+  // passed, otherwise the defaults would always win.
+  let explicit = |name: &str| cli.occurrences_of(name) > 0;
+
+  if let Some(admonitions) = boolean_value(&cli, "admonitions") {
+    launchpad = launchpad.admonitions(admonitions);
+  }
+
+  if explicit("base url") {
+    launchpad = launchpad.base_url(cli.value_of("base url").unwrap());
+  }
+
+  if let Some(compile_sass) = boolean_value(&cli, "compile sass") {
+    launchpad = launchpad.compile_sass(compile_sass);
+  }
+
+  if let Some(deny_broken_links) = boolean_value(&cli, "deny broken links") {
+    launchpad = launchpad.deny_broken_links(deny_broken_links);
+  }
+
+  if let Some(feeds) = cli.values_of("feeds") {
+    for feed in feeds {
+      launchpad = match feed {
+        "atom" => launchpad.feeds_atom(true),
+        "rss" => launchpad.feeds_rss(true),
+        _ => unreachable!(),
+      };
+    }
+  }
+
+  if let Some(heading_anchors) = boolean_value(&cli, "heading anchors") {
+    launchpad = launchpad.heading_anchors(heading_anchors);
+  }
+
+  if let Some(incremental) = boolean_value(&cli, "incremental") {
+    launchpad = launchpad.incremental(incremental);
+  }
+
+  if explicit("mode") {
+    launchpad = launchpad.mode(match cli.value_of("mode").unwrap() {
+      "development" => Mode::Development,
+      "production" => Mode::Production,
+      _ => unreachable!(),
+    });
+  }
+
+  if explicit("output") {
+    launchpad = launchpad.output(cli.value_of("output").unwrap());
+  }
+
+  if let Some(pretty_urls) = boolean_value(&cli, "pretty urls") {
+    launchpad = launchpad.pretty_urls(pretty_urls);
+  }
+
+  if let Some(robots_txt) = boolean_value(&cli, "robots txt") {
+    launchpad = launchpad.robots_txt(robots_txt);
+  }
+
+  if let Some(search_index) = boolean_value(&cli, "search index") {
+    launchpad = launchpad.search_index(search_index);
+  }
+
+  if let Some(sitemap) = boolean_value(&cli, "sitemap") {
+    launchpad = launchpad.sitemap(sitemap);
+  }
+
+  if explicit("source") {
+    launchpad = launchpad.source(cli.value_of("source").unwrap());
+  }
+
+  if let Some(statics) = cli.values_of("statics") {
     let mut converted = vec![];
     for input in statics {
       if !input.contains(':') {
@@ -119,20 +352,73 @@ source directory.",
       converted.push((split.next().unwrap(), split.next().unwrap()))
     }
 
-    converted
-  };
-  let templates_default = cli.value_of("templates default").unwrap();
-  let templates_directory = cli.value_of("templates directory").unwrap();
-
-  let launchpad = Launchpad::prepare()
-    .compile_sass(compile_sass)
-    .mode(mode)
-    .output(output)
-    .source(source)
-    .statics(statics)
-    .templates_default(templates_default)
-    .templates_directory(templates_directory)
-    .build()?;
-
-  launchpad.take_off()
+    launchpad = launchpad.statics(converted);
+  }
+
+  if let Some(taxonomies) = cli.values_of("taxonomies") {
+    launchpad = launchpad.taxonomies(taxonomies.collect());
+  }
+
+  if explicit("templates default") {
+    launchpad =
+      launchpad.templates_default(cli.value_of("templates default").unwrap());
+  }
+
+  if explicit("templates directory") {
+    launchpad = launchpad
+      .templates_directory(cli.value_of("templates directory").unwrap());
+  }
+
+  if explicit("threads") {
+    let threads = cli.value_of("threads").unwrap();
+    launchpad = launchpad.threads(threads.parse().unwrap_or_else(|_| {
+      eprintln!("Threads \"{}\" is not a valid number", threads);
+      std::process::exit(1);
+    }));
+  }
+
+  let mut launchpad = launchpad.build()?;
+  let documents = launchpad.take_off()?;
+
+  if cli.subcommand_matches("check").is_some() {
+    let broken = launchpad.check_links(&documents)?;
+    if broken.is_empty() {
+      println!("No broken links found");
+    } else {
+      for link in &broken {
+        eprintln!("{}", link);
+      }
+
+      eprintln!("{} broken links found", broken.len());
+      std::process::exit(1);
+    }
+  } else if let Some(serve) = cli.subcommand_matches("serve") {
+    let address = serve.value_of("address").unwrap();
+    println!("Serving {:?} at http://{}", launchpad.output, address);
+    launchpad.serve(address)?;
+  } else if cli.is_present("watch") {
+    launchpad.watch()?;
+  }
+
+  Ok(())
+}
+
+/// Makes a flag for a boolean option take an optional `=true` or `=false`, so
+/// it can also turn off an option that the configuration file turns on.
+fn boolean(arg: Arg<'static, 'static>) -> Arg<'static, 'static> {
+  arg
+    .takes_value(true)
+    .min_values(0)
+    .require_equals(true)
+    .possible_values(&["true", "false"])
+}
+
+/// Returns the value of a flag made with [`boolean`], or `None` when it wasn't
+/// passed.
+fn boolean_value(cli: &ArgMatches, name: &str) -> Option<bool> {
+  if cli.is_present(name) {
+    Some(cli.value_of(name) != Some("false"))
+  } else {
+    None
+  }
 }